@@ -1,28 +1,125 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::ffi::OsString;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
 use anyhow::{Context, Result};
 use beancount_formatter::configuration::{
-  Configuration, NewLineKind, PartialConfiguration as CorePartialConfiguration,
+  BlankLines, CommentAnchoring, Configuration, CurrencyPosition, DateSeparator,
+  LongCommentPlacement, LongCurrency, NewLineKind,
+  PartialConfiguration as CorePartialConfiguration, SameDateOrder, TagLinkGrouping,
+  ThousandsSeparator,
 };
-use beancount_formatter::format;
-use clap::Parser;
+use beancount_formatter::{
+  FormatError, LintOptions, SyntaxError, format, format_with_diagnostics,
+  include_paths, lint,
+};
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
 use toml::de::Error as TomlError;
 
 const SUPPORTED_EXTENSIONS: &[&str] = &["beancount", "bean"];
+/// Config files looked for in each directory, in order of precedence. All but
+/// `pyproject.toml` hold the options at the top level.
+const CONFIG_FILE_NAMES: &[&str] = &[
+  ".beancount-format.toml",
+  "beancount-format.toml",
+  "pyproject.toml",
+];
+/// How many lines at the top of a file are searched for `generated_marker`.
+const GENERATED_MARKER_LINES: usize = 5;
+
+/// How the result of `--check` is reported.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// A `checking failed: <path>` line on stderr for each unformatted file.
+  #[default]
+  Text,
+  /// A JSON array with the path of every checked file and whether it changed.
+  Json,
+}
+
+/// One entry of the `--format json` output.
+#[derive(Debug, serde::Serialize)]
+struct CheckEntry {
+  path: String,
+  changed: bool,
+}
 
 /// Simple CLI to format beancount files.
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct Cli {
-  /// Paths to beancount files or directories containing them.
+  /// Paths to beancount files or directories containing them. Use `-` to read
+  /// from stdin and write the formatted text to stdout.
   #[arg(value_name = "PATH", num_args = 1..)]
   pub input: Vec<PathBuf>,
+  /// Path to report for stdin input, used to find `pyproject.toml` and in
+  /// messages.
+  #[arg(long, value_name = "PATH")]
+  pub stdin_filepath: Option<PathBuf>,
+  /// Read options from this TOML file instead of the nearest `pyproject.toml`.
+  /// It holds either a `[tool.beancount-format]` table or the options at the
+  /// top level. Command line options still override it.
+  #[arg(long, value_name = "PATH")]
+  pub config: Option<PathBuf>,
   /// Check if files are formatted without modifying them.
   #[arg(long)]
   pub check: bool,
+  /// Print a unified diff of the changes instead of writing files.
+  #[arg(long, conflicts_with = "check")]
+  pub diff: bool,
+  /// Report lint warnings (e.g. duplicate metadata keys, unbalanced pushtag)
+  /// without formatting.
+  #[arg(long, conflicts_with_all = ["check", "diff"])]
+  pub lint: bool,
+  /// With --lint, warn about metadata values longer than N characters.
+  #[arg(long, value_name = "N", requires = "lint")]
+  pub warn_long_metadata: Option<usize>,
+  /// Also format files pulled in by `include` directives, recursively.
+  #[arg(long)]
+  pub follow_includes: bool,
+  /// Resolve relative include paths against this directory instead of the
+  /// directory of the including file.
+  #[arg(long, value_name = "DIR", requires = "follow_includes")]
+  pub include_root: Option<PathBuf>,
+  /// Only format files that differ from `HEAD` in git, or are untracked.
+  #[arg(long)]
+  pub changed: bool,
+  /// Only format files that differ from the given git ref, or are untracked.
+  #[arg(long, value_name = "REF")]
+  pub changed_since: Option<String>,
+  /// Format up to N files at once. Defaults to the number of CPUs.
+  #[arg(long, short = 'j', value_name = "N")]
+  pub jobs: Option<NonZeroUsize>,
+  /// Stop at the first file that can't be parsed or formatted instead of
+  /// reporting it and going on with the rest. Files are then handled one at a
+  /// time, so none after the failing one is touched.
+  #[arg(long)]
+  pub fail_fast: bool,
+  /// Skip files and directories matching this glob, relative to the input
+  /// directory. Can be repeated; adds to `exclude` in pyproject.toml.
+  #[arg(long, value_name = "GLOB")]
+  pub exclude: Vec<String>,
+  /// Leave files alone whose first lines contain this text, e.g.
+  /// `DO NOT EDIT`. Overrides `generated-marker` in pyproject.toml.
+  #[arg(long, value_name = "TEXT")]
+  pub generated_marker: Option<String>,
+  /// Don't print a message for each file; only the exit code tells the result.
+  #[arg(long, short = 'q')]
+  pub quiet: bool,
+  /// How --check reports its result. `json` prints an array of
+  /// `{"path", "changed"}` objects to stdout.
+  #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+  pub format: OutputFormat,
   /// Override maximum line width.
   #[arg(long, value_name = "WIDTH")]
   pub line_width: Option<u32>,
@@ -30,11 +127,144 @@ pub struct Cli {
   #[arg(long, value_name = "WIDTH")]
   pub indent_width: Option<u8>,
   /// Override newline style (lf or crlf).
-  #[arg(long, value_name = "STYLE", value_parser = NewLineKind::parse)]
-  pub new_line: Option<NewLineKind>,
+  #[arg(
+    long = "new-line",
+    value_name = "STYLE",
+    value_parser = NewLineKind::parse
+  )]
+  pub new_line_kind: Option<NewLineKind>,
   /// Remove empty lines between consecutive balance directives.
   #[arg(long)]
   pub compact_balance_spacing: bool,
+  /// Only normalize whitespace, keeping columns and token spacing as written.
+  #[arg(long)]
+  pub minimal: bool,
+  /// Normalize `key: value` shaped trailing comments.
+  #[arg(long)]
+  pub normalize_comment_metadata: bool,
+  /// Align posting currencies to start at this column.
+  #[arg(long, value_name = "COLUMN")]
+  pub currency_column: Option<u32>,
+  /// Collapse repeated spaces inside payee and narration strings.
+  #[arg(long)]
+  pub collapse_string_spaces: bool,
+  /// Align operands of consecutive single-operand directives into a column.
+  #[arg(long)]
+  pub align_keyword: bool,
+  /// Sort dated directives by date.
+  #[arg(long)]
+  pub sort_by_date: bool,
+  /// Tie-break for same-date directives when sorting: source or type_priority.
+  #[arg(long, value_name = "ORDER", value_parser = SameDateOrder::parse)]
+  pub same_date_order: Option<SameDateOrder>,
+  /// Keep trailing whitespace inside comments.
+  #[arg(long)]
+  pub no_trim_comment_trailing: bool,
+  /// Align trailing strings of consecutive text directives such as events.
+  #[arg(long)]
+  pub align_text_directives: bool,
+  /// Keep blank lines at the start of a file (at most --max-blank-lines).
+  #[arg(long)]
+  pub preserve_leading_blank_lines: bool,
+  /// Right-align amount numbers in a field of this width.
+  #[arg(long, value_name = "WIDTH")]
+  pub num_width: Option<u32>,
+  /// Put amounts exactly this many spaces after their account, without aligning.
+  #[arg(long, value_name = "SPACES")]
+  pub account_amount_spacing: Option<u32>,
+  /// Handling of amounts past the line width at the currency column: overflow or shrink.
+  #[arg(long, value_name = "MODE", value_parser = LongCurrency::parse)]
+  pub long_currency: Option<LongCurrency>,
+  /// Spaces between an amount's number and its currency.
+  #[arg(long, value_name = "SPACES")]
+  pub number_currency_spacing: Option<u8>,
+  /// Keep blank lines between postings and metadata inside transactions.
+  #[arg(long)]
+  pub preserve_posting_blank_lines: bool,
+  /// Currency position in amounts: after, or before (export-only, not valid beancount).
+  #[arg(long, value_name = "POSITION", value_parser = CurrencyPosition::parse)]
+  pub currency_position: Option<CurrencyPosition>,
+  /// Rewrite backslashes in include filenames to forward slashes.
+  #[arg(long)]
+  pub normalize_include_paths: bool,
+  /// Pad posting indent, flag and account to this width before the amount.
+  #[arg(long, value_name = "WIDTH")]
+  pub prefix_width: Option<u32>,
+  /// Re-parse formatted output and fail if any directive changed meaning.
+  #[arg(long, visible_alias = "safe")]
+  pub verify_semantics: bool,
+  /// Indent with tabs instead of spaces.
+  #[arg(long)]
+  pub use_tabs: bool,
+  /// Align posting amounts after the longest account in the whole file.
+  #[arg(long)]
+  pub global_account_alignment: bool,
+  /// Honor `; col:N` comments as the currency column of the next directive.
+  #[arg(long)]
+  pub respect_column_hints: bool,
+  /// Re-indent metadata this many spaces deeper than its parent's child indent.
+  #[arg(long, value_name = "SPACES")]
+  pub metadata_extra_indent: Option<u8>,
+  /// Blank lines between directives: normalize or preserve.
+  #[arg(long, value_name = "MODE", value_parser = BlankLines::parse)]
+  pub blank_lines: Option<BlankLines>,
+  /// Leave posting amounts within this many columns of their aligned position alone.
+  #[arg(long, value_name = "COLUMNS")]
+  pub alignment_tolerance: Option<u32>,
+  /// Sort tags and links after a header alphabetically.
+  #[arg(long)]
+  pub sort_tags_links: bool,
+  /// Group tags and links after a header: source, tags_first or links_first.
+  #[arg(long, value_name = "MODE", value_parser = TagLinkGrouping::parse)]
+  pub tag_link_grouping: Option<TagLinkGrouping>,
+  /// Separator written in dates: - or /.
+  #[arg(long, value_name = "SEP", value_parser = DateSeparator::parse)]
+  pub date_separator: Option<DateSeparator>,
+  /// Move option/plugin/include to the top and sort dated directives by date.
+  #[arg(long)]
+  pub sort_directives: bool,
+  /// Sort the postings of each transaction by account name.
+  #[arg(long)]
+  pub sort_postings: bool,
+  /// Keep at most this many blank lines between directives.
+  #[arg(long, value_name = "LINES")]
+  pub max_blank_lines: Option<u32>,
+  /// Do not force a blank line around transactions.
+  #[arg(long)]
+  pub no_blank_lines_around_transactions: bool,
+  /// Align the values of consecutive custom directives into a column.
+  #[arg(long)]
+  pub align_custom: bool,
+  /// Start inline comments at this column.
+  #[arg(long, value_name = "COLUMN")]
+  pub comment_column: Option<u32>,
+  /// Comments move with the directive below (following) or above (preceding) when sorting.
+  #[arg(long, value_name = "MODE", value_parser = CommentAnchoring::parse)]
+  pub comment_anchoring: Option<CommentAnchoring>,
+  /// Put posting flags in the indentation so accounts line up.
+  #[arg(long)]
+  pub flag_in_margin: bool,
+  /// Keep comments overflowing line_width trailing, or move them above or below.
+  #[arg(long, value_name = "PLACEMENT", value_parser = LongCommentPlacement::parse)]
+  pub long_comment_placement: Option<LongCommentPlacement>,
+  /// Also apply date-separator to date values of metadata and custom directives.
+  #[arg(long)]
+  pub normalize_metadata_dates: bool,
+  /// Spaces between a price operator (@ or @@) and the price.
+  #[arg(long, value_name = "SPACES")]
+  pub price_operator_spacing: Option<u8>,
+  /// Keep directives whose formatting would only change whitespace between tokens.
+  #[arg(long)]
+  pub minimize_diff: bool,
+  /// Uppercase currencies, keeping ones that aren't valid currencies as written.
+  #[arg(long)]
+  pub normalize_currency_case: bool,
+  /// Thousands separators in numbers: preserve, none or comma.
+  #[arg(long, value_name = "STYLE", value_parser = ThousandsSeparator::parse)]
+  pub thousands_separator: Option<ThousandsSeparator>,
+  /// Do not end the output with a newline.
+  #[arg(long)]
+  pub no_trailing_newline: bool,
 }
 
 /// Run the formatter CLI with a custom argument iterator.
@@ -47,70 +277,446 @@ where
   execute(parsed)
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Where relative `include` paths are resolved from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncludeBase {
+  /// The directory of the file containing the `include`, like beancount does.
+  IncludingFile,
+  /// A fixed root directory, e.g. the project root of a ledger subtree.
+  Root(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct RunOutcome {
   pub any_changed: bool,
+  /// Set when `--check` skipped a file it could not parse.
+  pub any_parse_error: bool,
+  /// Set when a file could not be read, formatted or written.
+  pub any_failed: bool,
 }
 
 fn execute(args: Cli) -> Result<RunOutcome> {
   let cli_overrides = args.overrides();
-  let config = load_configuration(&args.input, &cli_overrides)?;
-  let files = collect_files(&args.input)?;
+  if args.reads_stdin() {
+    return execute_stdin(&args, &cli_overrides);
+  }
+  let pyproject_partial = match &args.config {
+    Some(path) => Some(read_config_file(path)?),
+    None => parse_pyproject_partial(&args.input)?,
+  };
+  let pyproject_excludes = pyproject_partial
+    .as_ref()
+    .and_then(|partial| partial.exclude.as_deref())
+    .unwrap_or_default();
+  let excludes = build_excludes(pyproject_excludes.iter().chain(&args.exclude))?;
+  let generated_marker = args.generated_marker.clone().or_else(|| {
+    pyproject_partial
+      .as_ref()
+      .and_then(|partial| partial.generated_marker.clone())
+  });
+  let config = resolve_final_configuration(pyproject_partial, &cli_overrides);
+  let mut files = collect_files(&args.input, &excludes)?;
+  if args.follow_includes {
+    files = follow_includes(files, &args.include_base(), &excludes)?;
+  }
+  if let Some(base) = args.changed_base() {
+    files = filter_changed_files(files, base)?;
+  }
+  let lint_options = LintOptions {
+    max_metadata_value_width: args.warn_long_metadata,
+  };
+  let jobs = args
+    .jobs
+    .or_else(|| thread::available_parallelism().ok())
+    .map_or(1, NonZeroUsize::get);
+
+  let process = |path: &Path| {
+    process_file(
+      path,
+      &args,
+      &config,
+      &lint_options,
+      generated_marker.as_deref(),
+    )
+  };
+  let results = if args.fail_fast {
+    // Go one file at a time so nothing after the first failure is touched.
+    let mut results = Vec::with_capacity(files.len());
+    for path in &files {
+      let result = process(path);
+      let failed = result.is_err();
+      results.push(result);
+      if failed {
+        break;
+      }
+    }
+    results
+  } else {
+    run_parallel(&files, jobs, process)
+  };
+
+  // Report in file order no matter which worker finished first.
   let mut any_changed = false;
+  let mut any_parse_error = false;
+  let mut any_failed = false;
+  let mut check_entries = Vec::new();
+  for (path, result) in files.iter().zip(results) {
+    let report = match result {
+      Ok(report) => report,
+      Err(err) if args.fail_fast => return Err(err),
+      Err(err) => {
+        if !args.quiet {
+          eprintln!("error: {}: {:#}", to_posix_path(path), err);
+        }
+        any_failed = true;
+        continue;
+      }
+    };
+    print!("{}", report.stdout);
+    if !args.quiet {
+      eprint!("{}", report.stderr);
+    }
+    any_changed |= report.changed;
+    any_parse_error |= report.parse_error;
+    check_entries.push(CheckEntry {
+      path: to_posix_path(path),
+      changed: report.changed,
+    });
+  }
+  if args.check && args.format == OutputFormat::Json {
+    println!("{}", serde_json::to_string(&check_entries)?);
+  }
 
-  for path in files {
-    let content = fs::read_to_string(&path)
-      .with_context(|| format!("Failed to read {}", path.display()))?;
-    let path_display = to_posix_path(&path);
-    let formatted = format(&content, &config)?;
-    let changed = formatted != content;
-
-    if args.check {
-      if changed {
-        any_changed = true;
-        eprintln!("checking failed: {}", path_display);
+  Ok(RunOutcome {
+    any_changed,
+    any_parse_error,
+    any_failed,
+  })
+}
+
+/// What handling one file produced, printed once all files are done.
+#[derive(Debug, Default)]
+struct FileReport {
+  changed: bool,
+  parse_error: bool,
+  stdout: String,
+  stderr: String,
+}
+
+fn process_file(
+  path: &Path,
+  args: &Cli,
+  config: &Configuration,
+  lint_options: &LintOptions,
+  generated_marker: Option<&str>,
+) -> Result<FileReport> {
+  let content = fs::read_to_string(path)
+    .with_context(|| format!("Failed to read {}", path.display()))?;
+  let path_display = to_posix_path(path);
+  let mut report = FileReport::default();
+
+  if let Some(marker) = generated_marker
+    && content
+      .lines()
+      .take(GENERATED_MARKER_LINES)
+      .any(|line| line.contains(marker))
+  {
+    report.stderr = format!("skipped generated file: {}\n", path_display);
+    return Ok(report);
+  }
+
+  if args.lint {
+    for warning in lint(&content, lint_options) {
+      report
+        .stderr
+        .push_str(&format!("{}:{}\n", path_display, warning));
+    }
+    return Ok(report);
+  }
+
+  let formatted = match format_source(&content, &path_display, args, config)? {
+    Ok(formatted) => formatted,
+    Err(errors) => {
+      for error in errors {
+        report
+          .stderr
+          .push_str(&format!("parse error: {}: {}\n", path_display, error));
       }
-      continue;
+      report.parse_error = true;
+      return Ok(report);
     }
+  };
+  report.changed = formatted != content;
+  if !report.changed {
+    return Ok(report);
+  }
 
-    if changed {
-      eprintln!("formatting: {}", path_display);
+  if args.check {
+    report.stderr = format!("checking failed: {}\n", path_display);
+  } else if args.diff {
+    report.stdout = unified_diff(&content, &formatted, &path_display);
+  } else {
+    report.stderr = format!("formatting: {}\n", path_display);
+    fs::write(path, &formatted)
+      .with_context(|| format!("Failed to write {}", path.display()))?;
+  }
 
-      fs::write(&path, &formatted)
-        .with_context(|| format!("Failed to write {}", path.display()))?;
-      any_changed = true;
+  Ok(report)
+}
+
+/// Formats one file or stdin. Only `--check` tells unparseable sources apart
+/// from unformatted ones and returns their syntax errors, or fails on them
+/// with `--fail-fast`; when formatting, entries the parser can't read are kept
+/// as written.
+fn format_source(
+  content: &str,
+  path_display: &str,
+  args: &Cli,
+  config: &Configuration,
+) -> Result<Result<String, Vec<SyntaxError>>> {
+  if !args.check {
+    return format(content, config)
+      .map(Ok)
+      .with_context(|| format!("Failed to format {}", path_display));
+  }
+  match format_with_diagnostics(content, config) {
+    Ok(formatted) => Ok(Ok(formatted)),
+    Err(FormatError::Syntax(errors)) if args.fail_fast => {
+      anyhow::bail!("Failed to parse {}: {}", path_display, errors[0]);
     }
+    Err(FormatError::Syntax(errors)) => Ok(Err(errors)),
+    Err(FormatError::Other(err)) => Err(err),
   }
+}
+
+/// Runs `work` on every file with up to `jobs` threads and returns the results
+/// in the order of `files`.
+fn run_parallel<T, F>(files: &[PathBuf], jobs: usize, work: F) -> Vec<T>
+where
+  T: Send,
+  F: Fn(&Path) -> T + Sync,
+{
+  let next = AtomicUsize::new(0);
+  let (sender, receiver) = mpsc::channel();
+
+  thread::scope(|scope| {
+    for _ in 0..jobs.clamp(1, files.len().max(1)) {
+      let sender = sender.clone();
+      let (next, work) = (&next, &work);
+      scope.spawn(move || {
+        loop {
+          let idx = next.fetch_add(1, Ordering::Relaxed);
+          let Some(path) = files.get(idx) else {
+            break;
+          };
+          // The receiver outlives every worker, so sending can't fail.
+          let _ = sender.send((idx, work(path)));
+        }
+      });
+    }
+  });
+  drop(sender);
 
-  Ok(RunOutcome { any_changed })
+  let mut results: Vec<(usize, T)> = receiver.into_iter().collect();
+  results.sort_by_key(|(idx, _)| *idx);
+  results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Formats the text on stdin and writes it to stdout. With `--check` it only
+/// reports whether the text is formatted, with `--diff` it prints the changes.
+fn execute_stdin(
+  args: &Cli,
+  overrides: &CliPartialConfiguration,
+) -> Result<RunOutcome> {
+  if args.input.len() > 1 {
+    anyhow::bail!("`-` (stdin) can't be combined with other paths");
+  }
+  if args.follow_includes || args.changed_base().is_some() {
+    anyhow::bail!("--follow-includes and --changed can't be used with stdin");
+  }
+
+  // The virtual path doesn't need to exist; look for `pyproject.toml` from its
+  // directory first, then fall back to the usual lookup from the cwd.
+  let pyproject_partial = match &args.config {
+    Some(path) => Some(read_config_file(path)?),
+    None => read_pyproject_partial(
+      args
+        .stdin_filepath
+        .as_deref()
+        .and_then(Path::parent)
+        .and_then(|dir| find_pyproject_from(dir.to_path_buf()))
+        .or_else(|| find_pyproject(&[])),
+    )?,
+  };
+  let config = resolve_final_configuration(pyproject_partial, overrides);
+  let path_display = args
+    .stdin_filepath
+    .as_deref()
+    .map_or_else(|| "<stdin>".to_string(), to_posix_path);
+
+  let mut content = String::new();
+  io::stdin()
+    .read_to_string(&mut content)
+    .context("Failed to read stdin")?;
+
+  if args.lint {
+    let lint_options = LintOptions {
+      max_metadata_value_width: args.warn_long_metadata,
+    };
+    for warning in lint(&content, &lint_options) {
+      eprintln!("{}:{}", path_display, warning);
+    }
+    return Ok(RunOutcome::default());
+  }
+
+  let formatted = match format_source(&content, &path_display, args, &config)? {
+    Ok(formatted) => formatted,
+    Err(errors) => {
+      if !args.quiet {
+        for error in errors {
+          eprintln!("parse error: {}: {}", path_display, error);
+        }
+      }
+      return Ok(RunOutcome {
+        any_parse_error: true,
+        ..RunOutcome::default()
+      });
+    }
+  };
+
+  let changed = formatted != content;
+  if args.check {
+    if args.format == OutputFormat::Json {
+      let entries = [CheckEntry {
+        path: path_display,
+        changed,
+      }];
+      println!("{}", serde_json::to_string(&entries)?);
+    } else if changed && !args.quiet {
+      eprintln!("checking failed: {}", path_display);
+    }
+    return Ok(RunOutcome {
+      any_changed: changed,
+      ..RunOutcome::default()
+    });
+  }
+  if args.diff {
+    if changed {
+      print!("{}", unified_diff(&content, &formatted, &path_display));
+    }
+    return Ok(RunOutcome {
+      any_changed: changed,
+      ..RunOutcome::default()
+    });
+  }
+
+  io::stdout()
+    .write_all(formatted.as_bytes())
+    .context("Failed to write stdout")?;
+  Ok(RunOutcome::default())
+}
+
+/// A unified diff from `original` to `formatted`, with `path` in both headers.
+fn unified_diff(original: &str, formatted: &str, path: &str) -> String {
+  similar::TextDiff::from_lines(original, formatted)
+    .unified_diff()
+    .header(path, path)
+    .to_string()
 }
 
 impl Cli {
+  fn reads_stdin(&self) -> bool {
+    self.input.iter().any(|path| path.as_os_str() == "-")
+  }
+
+  fn include_base(&self) -> IncludeBase {
+    match &self.include_root {
+      Some(root) => IncludeBase::Root(root.clone()),
+      None => IncludeBase::IncludingFile,
+    }
+  }
+
+  fn changed_base(&self) -> Option<&str> {
+    self
+      .changed_since
+      .as_deref()
+      .or(self.changed.then_some("HEAD"))
+  }
+
   fn overrides(&self) -> CliPartialConfiguration {
     CliPartialConfiguration {
       line_width: self.line_width,
       indent_width: self.indent_width,
-      new_line: self.new_line,
+      new_line_kind: self.new_line_kind,
       compact_balance_spacing: self.compact_balance_spacing.then_some(true),
+      minimal: self.minimal.then_some(true),
+      normalize_comment_metadata: self.normalize_comment_metadata.then_some(true),
+      currency_column: self.currency_column,
+      collapse_string_spaces: self.collapse_string_spaces.then_some(true),
+      align_keyword: self.align_keyword.then_some(true),
+      sort_by_date: self.sort_by_date.then_some(true),
+      same_date_order: self.same_date_order,
+      trim_comment_trailing: self.no_trim_comment_trailing.then_some(false),
+      align_text_directives: self.align_text_directives.then_some(true),
+      preserve_leading_blank_lines: self.preserve_leading_blank_lines.then_some(true),
+      num_width: self.num_width,
+      account_amount_spacing: self.account_amount_spacing,
+      long_currency: self.long_currency,
+      number_currency_spacing: self.number_currency_spacing,
+      preserve_posting_blank_lines: self.preserve_posting_blank_lines.then_some(true),
+      currency_position: self.currency_position,
+      normalize_include_paths: self.normalize_include_paths.then_some(true),
+      prefix_width: self.prefix_width,
+      verify_semantics: self.verify_semantics.then_some(true),
+      use_tabs: self.use_tabs.then_some(true),
+      global_account_alignment: self.global_account_alignment.then_some(true),
+      respect_column_hints: self.respect_column_hints.then_some(true),
+      metadata_extra_indent: self.metadata_extra_indent,
+      blank_lines: self.blank_lines,
+      alignment_tolerance: self.alignment_tolerance,
+      sort_tags_links: self.sort_tags_links.then_some(true),
+      tag_link_grouping: self.tag_link_grouping,
+      date_separator: self.date_separator,
+      sort_directives: self.sort_directives.then_some(true),
+      sort_postings: self.sort_postings.then_some(true),
+      max_blank_lines: self.max_blank_lines,
+      blank_lines_around_transactions: self
+        .no_blank_lines_around_transactions
+        .then_some(false),
+      align_custom: self.align_custom.then_some(true),
+      comment_column: self.comment_column,
+      comment_anchoring: self.comment_anchoring,
+      flag_in_margin: self.flag_in_margin.then_some(true),
+      long_comment_placement: self.long_comment_placement,
+      normalize_metadata_dates: self.normalize_metadata_dates.then_some(true),
+      price_operator_spacing: self.price_operator_spacing,
+      minimize_diff: self.minimize_diff.then_some(true),
+      normalize_currency_case: self.normalize_currency_case.then_some(true),
+      thousands_separator: self.thousands_separator,
+      trailing_newline: self.no_trailing_newline.then_some(false),
     }
   }
 }
 
-fn load_configuration(
+fn parse_pyproject_partial(
   inputs: &[PathBuf],
-  overrides: &CliPartialConfiguration,
-) -> Result<Configuration> {
-  let pyproject_partial = parse_pyproject_partial(inputs)?;
-  Ok(resolve_final_configuration(pyproject_partial, overrides))
+) -> Result<Option<PyprojectPartialConfiguration>> {
+  read_pyproject_partial(find_pyproject(inputs))
 }
 
-fn parse_pyproject_partial(
-  inputs: &[PathBuf],
+fn read_pyproject_partial(
+  pyproject_path: Option<PathBuf>,
 ) -> Result<Option<PyprojectPartialConfiguration>> {
-  if let Some(pyproject_path) = find_pyproject(inputs) {
+  if let Some(pyproject_path) = pyproject_path {
     let content = fs::read_to_string(&pyproject_path)
       .with_context(|| format!("Failed to read {}", pyproject_path.display()))?;
 
+    if !pyproject_path.ends_with("pyproject.toml") {
+      let partial = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", pyproject_path.display()))?;
+      return Ok(Some(partial));
+    }
+
     let parsed = parse_pyproject(&content)
       .with_context(|| format!("Failed to parse {}", pyproject_path.display()))?;
 
@@ -122,6 +728,25 @@ fn parse_pyproject_partial(
   Ok(None)
 }
 
+/// Reads the file given with `--config`: a `[tool.beancount-format]` table
+/// when the file has a `tool` table, otherwise options at the top level.
+fn read_config_file(path: &Path) -> Result<PyprojectPartialConfiguration> {
+  if !path.is_file() {
+    anyhow::bail!("Config file {} does not exist", path.display());
+  }
+  let content = fs::read_to_string(path)
+    .with_context(|| format!("Failed to read {}", path.display()))?;
+  let parsed = parse_pyproject(&content)
+    .with_context(|| format!("Failed to parse {}", path.display()))?;
+  match parsed.tool {
+    Some(tool) => tool.beancount_formatter.with_context(|| {
+      format!("{} has no [tool.beancount-format] table", path.display())
+    }),
+    None => toml::from_str(&content)
+      .with_context(|| format!("Failed to parse {}", path.display())),
+  }
+}
+
 fn resolve_final_configuration(
   config_file: Option<PyprojectPartialConfiguration>,
   cli_opt: &CliPartialConfiguration,
@@ -131,20 +756,101 @@ fn resolve_final_configuration(
   let final_partial = CorePartialConfiguration {
     line_width: cli_opt.line_width.or(config_opt.line_width),
     indent_width: cli_opt.indent_width.or(config_opt.indent_width),
-    new_line: cli_opt.new_line.or(config_opt.new_line),
+    new_line_kind: cli_opt.new_line_kind.or(config_opt.new_line_kind),
     compact_balance_spacing: cli_opt
       .compact_balance_spacing
       .or(config_opt.compact_balance_spacing),
+    minimal: cli_opt.minimal.or(config_opt.minimal),
+    normalize_comment_metadata: cli_opt
+      .normalize_comment_metadata
+      .or(config_opt.normalize_comment_metadata),
+    currency_column: cli_opt.currency_column.or(config_opt.currency_column),
+    collapse_string_spaces: cli_opt
+      .collapse_string_spaces
+      .or(config_opt.collapse_string_spaces),
+    align_keyword: cli_opt.align_keyword.or(config_opt.align_keyword),
+    sort_by_date: cli_opt.sort_by_date.or(config_opt.sort_by_date),
+    same_date_order: cli_opt.same_date_order.or(config_opt.same_date_order),
+    trim_comment_trailing: cli_opt
+      .trim_comment_trailing
+      .or(config_opt.trim_comment_trailing),
+    align_text_directives: cli_opt
+      .align_text_directives
+      .or(config_opt.align_text_directives),
+    preserve_leading_blank_lines: cli_opt
+      .preserve_leading_blank_lines
+      .or(config_opt.preserve_leading_blank_lines),
+    num_width: cli_opt.num_width.or(config_opt.num_width),
+    account_amount_spacing: cli_opt
+      .account_amount_spacing
+      .or(config_opt.account_amount_spacing),
+    long_currency: cli_opt.long_currency.or(config_opt.long_currency),
+    number_currency_spacing: cli_opt
+      .number_currency_spacing
+      .or(config_opt.number_currency_spacing),
+    preserve_posting_blank_lines: cli_opt
+      .preserve_posting_blank_lines
+      .or(config_opt.preserve_posting_blank_lines),
+    currency_position: cli_opt.currency_position.or(config_opt.currency_position),
+    normalize_include_paths: cli_opt
+      .normalize_include_paths
+      .or(config_opt.normalize_include_paths),
+    prefix_width: cli_opt.prefix_width.or(config_opt.prefix_width),
+    verify_semantics: cli_opt.verify_semantics.or(config_opt.verify_semantics),
+    use_tabs: cli_opt.use_tabs.or(config_opt.use_tabs),
+    global_account_alignment: cli_opt
+      .global_account_alignment
+      .or(config_opt.global_account_alignment),
+    respect_column_hints: cli_opt
+      .respect_column_hints
+      .or(config_opt.respect_column_hints),
+    metadata_extra_indent: cli_opt
+      .metadata_extra_indent
+      .or(config_opt.metadata_extra_indent),
+    blank_lines: cli_opt.blank_lines.or(config_opt.blank_lines),
+    alignment_tolerance: cli_opt
+      .alignment_tolerance
+      .or(config_opt.alignment_tolerance),
+    sort_tags_links: cli_opt.sort_tags_links.or(config_opt.sort_tags_links),
+    tag_link_grouping: cli_opt.tag_link_grouping.or(config_opt.tag_link_grouping),
+    date_separator: cli_opt.date_separator.or(config_opt.date_separator),
+    sort_directives: cli_opt.sort_directives.or(config_opt.sort_directives),
+    sort_postings: cli_opt.sort_postings.or(config_opt.sort_postings),
+    max_blank_lines: cli_opt.max_blank_lines.or(config_opt.max_blank_lines),
+    blank_lines_around_transactions: cli_opt
+      .blank_lines_around_transactions
+      .or(config_opt.blank_lines_around_transactions),
+    align_custom: cli_opt.align_custom.or(config_opt.align_custom),
+    comment_column: cli_opt.comment_column.or(config_opt.comment_column),
+    comment_anchoring: cli_opt.comment_anchoring.or(config_opt.comment_anchoring),
+    flag_in_margin: cli_opt.flag_in_margin.or(config_opt.flag_in_margin),
+    long_comment_placement: cli_opt
+      .long_comment_placement
+      .or(config_opt.long_comment_placement),
+    normalize_metadata_dates: cli_opt
+      .normalize_metadata_dates
+      .or(config_opt.normalize_metadata_dates),
+    price_operator_spacing: cli_opt
+      .price_operator_spacing
+      .or(config_opt.price_operator_spacing),
+    minimize_diff: cli_opt.minimize_diff.or(config_opt.minimize_diff),
+    normalize_currency_case: cli_opt
+      .normalize_currency_case
+      .or(config_opt.normalize_currency_case),
+    thousands_separator: cli_opt
+      .thousands_separator
+      .or(config_opt.thousands_separator),
+    trailing_newline: cli_opt.trailing_newline.or(config_opt.trailing_newline),
   };
 
   final_partial.resolve()
 }
 
-fn collect_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+fn collect_files(inputs: &[PathBuf], excludes: &GlobSet) -> Result<Vec<PathBuf>> {
   let mut files = Vec::new();
 
   for input in inputs {
-    collect_path(input, &mut files)?;
+    collect_path(input, excludes, &mut files)?;
   }
 
   if files.is_empty() {
@@ -154,6 +860,191 @@ fn collect_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
   Ok(files)
 }
 
+/// Add every file reachable through `include` directives of `files`. Each file
+/// is listed once, even when several files include it. Included files go
+/// through the same extension and `excludes` filters as the ones found in
+/// directories; a file filtered out is neither formatted nor followed.
+fn follow_includes(
+  files: Vec<PathBuf>,
+  base: &IncludeBase,
+  excludes: &GlobSet,
+) -> Result<Vec<PathBuf>> {
+  let mut seen = HashSet::new();
+  let mut result = Vec::new();
+  let mut queue: VecDeque<PathBuf> = files.into();
+
+  while let Some(path) = queue.pop_front() {
+    let canonical = fs::canonicalize(&path)
+      .with_context(|| format!("Failed to resolve {}", path.display()))?;
+    if !seen.insert(canonical) {
+      continue;
+    }
+
+    let content = fs::read_to_string(&path)
+      .with_context(|| format!("Failed to read {}", path.display()))?;
+    let dir = include_dir(&path, base);
+    for include in include_paths(&content) {
+      for target in include_targets(&path, &dir, &include)? {
+        let relative = target.strip_prefix(&dir).unwrap_or(&target);
+        if is_supported_file(&target)
+          && !excludes.is_match(&target)
+          && !excludes.is_match(relative)
+        {
+          queue.push_back(target);
+        }
+      }
+    }
+    result.push(path);
+  }
+
+  Ok(result)
+}
+
+/// The directory relative `include` paths of `including` are resolved from.
+fn include_dir(including: &Path, base: &IncludeBase) -> PathBuf {
+  match base {
+    IncludeBase::IncludingFile => including
+      .parent()
+      .unwrap_or_else(|| Path::new(""))
+      .to_path_buf(),
+    IncludeBase::Root(root) => root.clone(),
+  }
+}
+
+/// The files `include` names, resolved from `dir`. Like beancount, a pattern
+/// such as `accounts/*.beancount` includes every matching file, in path order;
+/// `*` and `?` don't cross `/`.
+fn include_targets(
+  including: &Path,
+  dir: &Path,
+  include: &str,
+) -> Result<Vec<PathBuf>> {
+  let target = dir.join(include);
+  if !include.contains(['*', '?', '[']) {
+    if !target.is_file() {
+      anyhow::bail!(
+        "{} includes {}, which does not exist",
+        to_posix_path(including),
+        to_posix_path(&target)
+      );
+    }
+    return Ok(vec![target]);
+  }
+
+  let pattern = to_posix_path(&target);
+  let matcher = GlobBuilder::new(&pattern)
+    .literal_separator(true)
+    .build()
+    .with_context(|| {
+      format!(
+        "{} includes an invalid pattern {include:?}",
+        to_posix_path(including)
+      )
+    })?
+    .compile_matcher();
+  // Walk from the deepest directory that comes before the first wildcard.
+  let root: PathBuf = target
+    .components()
+    .take_while(|part| !part.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+    .collect();
+  let mut targets = Vec::new();
+  if root.is_dir() {
+    let walker = WalkBuilder::new(&root)
+      .standard_filters(false)
+      .follow_links(false)
+      .sort_by_file_path(|a, b| a.cmp(b))
+      .build();
+    for entry in walker {
+      let entry = entry
+        .with_context(|| format!("Failed to read directory {}", root.display()))?;
+      let path = entry.path();
+      if path.is_file() && matcher.is_match(to_posix_path(path)) {
+        targets.push(path.to_path_buf());
+      }
+    }
+  }
+  if targets.is_empty() {
+    anyhow::bail!(
+      "{} includes {}, which matches no file",
+      to_posix_path(including),
+      pattern
+    );
+  }
+  Ok(targets)
+}
+
+/// Keep only the files git reports as changed since `base`: those `git diff
+/// --name-only <base>` lists plus untracked ones that aren't ignored. Git runs
+/// in the repository of each file, whatever the current directory is.
+fn filter_changed_files(files: Vec<PathBuf>, base: &str) -> Result<Vec<PathBuf>> {
+  let mut repo_roots: HashMap<PathBuf, PathBuf> = HashMap::new();
+  let mut changed: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
+  let mut result = Vec::new();
+
+  for path in files {
+    let canonical = fs::canonicalize(&path)
+      .with_context(|| format!("Failed to resolve {}", path.display()))?;
+    let dir = canonical.parent().unwrap_or(&canonical).to_path_buf();
+    let root = match repo_roots.get(&dir) {
+      Some(root) => root.clone(),
+      None => {
+        let toplevel = run_git(&dir, &["rev-parse", "--show-toplevel"])
+          .context("--changed requires running inside a git repository")?;
+        let root = fs::canonicalize(toplevel.trim())
+          .with_context(|| format!("Failed to resolve {}", toplevel.trim()))?;
+        repo_roots.insert(dir, root.clone());
+        root
+      }
+    };
+    if !changed.contains_key(&root) {
+      let files = changed_in_repo(&root, base)?;
+      changed.insert(root.clone(), files);
+    }
+    if changed[&root].contains(&canonical) {
+      result.push(path);
+    }
+  }
+
+  Ok(result)
+}
+
+/// Canonical paths of the files in the repository at `root` that changed since
+/// `base` or are untracked.
+fn changed_in_repo(root: &Path, base: &str) -> Result<HashSet<PathBuf>> {
+  let diff = run_git(root, &["diff", "--name-only", base, "--"])?;
+  let untracked = run_git(root, &["ls-files", "--others", "--exclude-standard"])?;
+
+  // Both list paths relative to `root`. Deleted files can't be canonicalized
+  // and have nothing to format anyway.
+  Ok(
+    diff
+      .lines()
+      .chain(untracked.lines())
+      .filter(|line| !line.is_empty())
+      .filter_map(|line| fs::canonicalize(root.join(line)).ok())
+      .collect(),
+  )
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+  let output = Command::new("git")
+    .arg("-C")
+    .arg(dir)
+    .args(args)
+    .output()
+    .context("Failed to run git")?;
+
+  if !output.status.success() {
+    anyhow::bail!(
+      "git {} failed: {}",
+      args.join(" "),
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+
+  Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 fn find_pyproject(inputs: &[PathBuf]) -> Option<PathBuf> {
   let mut roots = Vec::new();
 
@@ -173,58 +1064,88 @@ fn find_pyproject(inputs: &[PathBuf]) -> Option<PathBuf> {
     }
   }
 
-  for mut dir in roots {
-    loop {
-      let candidate = dir.join("pyproject.toml");
-      if candidate.is_file() {
-        return Some(candidate);
-      }
+  roots.into_iter().find_map(find_pyproject_from)
+}
 
-      if !dir.pop() {
-        break;
-      }
+/// The nearest config file in `dir` or one of its ancestors. Within one
+/// directory the standalone files win over `pyproject.toml`.
+fn find_pyproject_from(mut dir: PathBuf) -> Option<PathBuf> {
+  loop {
+    let candidate = CONFIG_FILE_NAMES
+      .iter()
+      .map(|name| dir.join(name))
+      .find(|candidate| candidate.is_file());
+    if candidate.is_some() {
+      return candidate;
     }
-  }
 
-  None
+    if !dir.pop() {
+      return None;
+    }
+  }
 }
 
-fn collect_path(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+fn collect_path(
+  path: &Path,
+  excludes: &GlobSet,
+  files: &mut Vec<PathBuf>,
+) -> Result<()> {
   let metadata = fs::metadata(path)
     .with_context(|| format!("Failed to access {}", path.display()))?;
 
   if metadata.is_dir() {
-    collect_dir(path, files)?;
-  } else if metadata.is_file() && is_supported_file(path) {
+    collect_dir(path, excludes, files)?;
+  } else if metadata.is_file() && is_supported_file(path) && !excludes.is_match(path) {
     files.push(path.to_path_buf());
   }
 
   Ok(())
 }
 
-fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
-  let mut entries = fs::read_dir(dir)
-    .with_context(|| format!("Failed to read directory {}", dir.display()))?
-    .collect::<Result<Vec<_>, _>>()?;
-
-  entries.sort_by_key(|a| a.path());
+/// Walks `dir` in path order, skipping what `.gitignore` files or `excludes`
+/// rule out. Symlinked directories are not followed.
+fn collect_dir(dir: &Path, excludes: &GlobSet, files: &mut Vec<PathBuf>) -> Result<()> {
+  let root = dir.to_path_buf();
+  let filter_excludes = excludes.clone();
+  let walker = WalkBuilder::new(dir)
+    .hidden(false)
+    .require_git(false)
+    .follow_links(false)
+    .sort_by_file_path(|a, b| a.cmp(b))
+    .filter_entry(move |entry| {
+      let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+      !filter_excludes.is_match(relative)
+    })
+    .build();
 
-  for entry in entries {
+  for entry in walker {
+    let entry =
+      entry.with_context(|| format!("Failed to read directory {}", dir.display()))?;
     let path = entry.path();
-    let metadata = entry
-      .metadata()
-      .with_context(|| format!("Failed to access {}", path.display()))?;
-
-    if metadata.is_dir() {
-      collect_dir(&path, files)?;
-    } else if metadata.is_file() && is_supported_file(&path) {
-      files.push(path);
+    if !entry.file_type().is_some_and(|kind| kind.is_dir())
+      && path.is_file()
+      && is_supported_file(path)
+    {
+      files.push(path.to_path_buf());
     }
   }
 
   Ok(())
 }
 
+/// Compiles the `exclude` globs of pyproject.toml and the command line.
+fn build_excludes<'g>(globs: impl IntoIterator<Item = &'g String>) -> Result<GlobSet> {
+  let mut builder = GlobSetBuilder::new();
+  for glob in globs {
+    builder.add(
+      Glob::new(glob).with_context(|| format!("Invalid exclude pattern {glob:?}"))?,
+    );
+  }
+  builder
+    .build()
+    .context("Failed to compile exclude patterns")
+}
+
 fn is_supported_file(path: &Path) -> bool {
   path
     .extension()
@@ -245,23 +1166,158 @@ struct ToolSection {
 }
 
 #[derive(Debug, Default, Clone, serde::Deserialize)]
+// A misspelled option fails loudly instead of being silently ignored.
+#[serde(deny_unknown_fields)]
 struct PyprojectPartialConfiguration {
+  /// Globs of files and directories to skip; not a formatting option.
+  exclude: Option<Vec<String>>,
+  /// Marker of generated files to leave alone; not a formatting option.
+  #[serde(rename = "generated-marker")]
+  generated_marker: Option<String>,
   #[serde(rename = "line-width")]
   line_width: Option<u32>,
   #[serde(rename = "indent-width")]
   indent_width: Option<u8>,
   #[serde(rename = "new-line-kind")]
-  new_line: Option<beancount_formatter::configuration::NewLineKind>,
+  new_line_kind: Option<beancount_formatter::configuration::NewLineKind>,
   #[serde(rename = "compact-balance-spacing")]
   compact_balance_spacing: Option<bool>,
+  minimal: Option<bool>,
+  #[serde(rename = "normalize-comment-metadata")]
+  normalize_comment_metadata: Option<bool>,
+  #[serde(rename = "currency-column")]
+  currency_column: Option<u32>,
+  #[serde(rename = "collapse-string-spaces")]
+  collapse_string_spaces: Option<bool>,
+  #[serde(rename = "align-keyword")]
+  align_keyword: Option<bool>,
+  #[serde(rename = "sort-by-date")]
+  sort_by_date: Option<bool>,
+  #[serde(rename = "same-date-order")]
+  same_date_order: Option<SameDateOrder>,
+  #[serde(rename = "trim-comment-trailing")]
+  trim_comment_trailing: Option<bool>,
+  #[serde(rename = "align-text-directives")]
+  align_text_directives: Option<bool>,
+  #[serde(rename = "preserve-leading-blank-lines")]
+  preserve_leading_blank_lines: Option<bool>,
+  #[serde(rename = "num-width")]
+  num_width: Option<u32>,
+  #[serde(rename = "account-amount-spacing")]
+  account_amount_spacing: Option<u32>,
+  #[serde(rename = "long-currency")]
+  long_currency: Option<LongCurrency>,
+  #[serde(rename = "number-currency-spacing")]
+  number_currency_spacing: Option<u8>,
+  #[serde(rename = "preserve-posting-blank-lines")]
+  preserve_posting_blank_lines: Option<bool>,
+  #[serde(rename = "currency-position")]
+  currency_position: Option<CurrencyPosition>,
+  #[serde(rename = "normalize-include-paths")]
+  normalize_include_paths: Option<bool>,
+  #[serde(rename = "prefix-width")]
+  prefix_width: Option<u32>,
+  #[serde(rename = "verify-semantics")]
+  verify_semantics: Option<bool>,
+  #[serde(rename = "use-tabs")]
+  use_tabs: Option<bool>,
+  #[serde(rename = "global-account-alignment")]
+  global_account_alignment: Option<bool>,
+  #[serde(rename = "respect-column-hints")]
+  respect_column_hints: Option<bool>,
+  #[serde(rename = "metadata-extra-indent")]
+  metadata_extra_indent: Option<u8>,
+  #[serde(rename = "blank-lines")]
+  blank_lines: Option<BlankLines>,
+  #[serde(rename = "alignment-tolerance")]
+  alignment_tolerance: Option<u32>,
+  #[serde(rename = "sort-tags-links")]
+  sort_tags_links: Option<bool>,
+  #[serde(rename = "tag-link-grouping")]
+  tag_link_grouping: Option<TagLinkGrouping>,
+  #[serde(rename = "date-separator")]
+  date_separator: Option<DateSeparator>,
+  #[serde(rename = "sort-directives")]
+  sort_directives: Option<bool>,
+  #[serde(rename = "sort-postings")]
+  sort_postings: Option<bool>,
+  #[serde(rename = "max-blank-lines")]
+  max_blank_lines: Option<u32>,
+  #[serde(rename = "blank-lines-around-transactions")]
+  blank_lines_around_transactions: Option<bool>,
+  #[serde(rename = "align-custom")]
+  align_custom: Option<bool>,
+  #[serde(rename = "comment-column")]
+  comment_column: Option<u32>,
+  #[serde(rename = "comment-anchoring")]
+  comment_anchoring: Option<CommentAnchoring>,
+  #[serde(rename = "flag-in-margin")]
+  flag_in_margin: Option<bool>,
+  #[serde(rename = "long-comment-placement")]
+  long_comment_placement: Option<LongCommentPlacement>,
+  #[serde(rename = "normalize-metadata-dates")]
+  normalize_metadata_dates: Option<bool>,
+  #[serde(rename = "price-operator-spacing")]
+  price_operator_spacing: Option<u8>,
+  #[serde(rename = "minimize-diff")]
+  minimize_diff: Option<bool>,
+  #[serde(rename = "normalize-currency-case")]
+  normalize_currency_case: Option<bool>,
+  #[serde(rename = "thousands-separator")]
+  thousands_separator: Option<ThousandsSeparator>,
+  #[serde(rename = "trailing-newline")]
+  trailing_newline: Option<bool>,
 }
 
 #[derive(Debug, Default, Clone)]
 struct CliPartialConfiguration {
   line_width: Option<u32>,
   indent_width: Option<u8>,
-  new_line: Option<beancount_formatter::configuration::NewLineKind>,
+  new_line_kind: Option<beancount_formatter::configuration::NewLineKind>,
   compact_balance_spacing: Option<bool>,
+  minimal: Option<bool>,
+  normalize_comment_metadata: Option<bool>,
+  currency_column: Option<u32>,
+  collapse_string_spaces: Option<bool>,
+  align_keyword: Option<bool>,
+  sort_by_date: Option<bool>,
+  same_date_order: Option<SameDateOrder>,
+  trim_comment_trailing: Option<bool>,
+  align_text_directives: Option<bool>,
+  preserve_leading_blank_lines: Option<bool>,
+  num_width: Option<u32>,
+  account_amount_spacing: Option<u32>,
+  long_currency: Option<LongCurrency>,
+  number_currency_spacing: Option<u8>,
+  preserve_posting_blank_lines: Option<bool>,
+  currency_position: Option<CurrencyPosition>,
+  normalize_include_paths: Option<bool>,
+  prefix_width: Option<u32>,
+  verify_semantics: Option<bool>,
+  use_tabs: Option<bool>,
+  global_account_alignment: Option<bool>,
+  respect_column_hints: Option<bool>,
+  metadata_extra_indent: Option<u8>,
+  blank_lines: Option<BlankLines>,
+  alignment_tolerance: Option<u32>,
+  sort_tags_links: Option<bool>,
+  tag_link_grouping: Option<TagLinkGrouping>,
+  date_separator: Option<DateSeparator>,
+  sort_directives: Option<bool>,
+  sort_postings: Option<bool>,
+  max_blank_lines: Option<u32>,
+  blank_lines_around_transactions: Option<bool>,
+  align_custom: Option<bool>,
+  comment_column: Option<u32>,
+  comment_anchoring: Option<CommentAnchoring>,
+  flag_in_margin: Option<bool>,
+  long_comment_placement: Option<LongCommentPlacement>,
+  normalize_metadata_dates: Option<bool>,
+  price_operator_spacing: Option<u8>,
+  minimize_diff: Option<bool>,
+  normalize_currency_case: Option<bool>,
+  thousands_separator: Option<ThousandsSeparator>,
+  trailing_newline: Option<bool>,
 }
 
 fn parse_pyproject(content: &str) -> Result<Pyproject, TomlError> {
@@ -284,6 +1340,49 @@ mod tests {
   indent-width = 3
   new-line-kind = "crlf"
   compact-balance-spacing = true
+  minimal = true
+  normalize-comment-metadata = true
+  currency-column = 60
+  collapse-string-spaces = true
+  align-keyword = true
+  sort-by-date = true
+  same-date-order = "type_priority"
+  trim-comment-trailing = false
+  align-text-directives = true
+  preserve-leading-blank-lines = true
+  num-width = 12
+  account-amount-spacing = 4
+  long-currency = "shrink"
+  number-currency-spacing = 2
+  preserve-posting-blank-lines = true
+  currency-position = "before"
+  normalize-include-paths = true
+  prefix-width = 40
+  verify-semantics = true
+  use-tabs = true
+  global-account-alignment = true
+  respect-column-hints = true
+  metadata-extra-indent = 2
+  blank-lines = "preserve"
+  alignment-tolerance = 2
+  sort-tags-links = true
+  tag-link-grouping = "links_first"
+  date-separator = "/"
+  sort-directives = true
+  sort-postings = true
+  max-blank-lines = 1
+  blank-lines-around-transactions = false
+  align-custom = true
+  comment-column = 60
+  comment-anchoring = "preceding"
+  flag-in-margin = true
+  long-comment-placement = "above"
+  normalize-metadata-dates = true
+  price-operator-spacing = 0
+  minimize-diff = true
+  normalize-currency-case = true
+  thousands-separator = "none"
+  trailing-newline = false
 "#;
 
     let parsed = parse_pyproject(content).expect("pyproject should parse");
@@ -295,8 +1394,54 @@ mod tests {
 
     assert_eq!(cfg.line_width, Some(88));
     assert_eq!(cfg.indent_width, Some(3));
-    assert_eq!(cfg.new_line, Some(NewLineKind::CRLF));
+    assert_eq!(cfg.new_line_kind, Some(NewLineKind::CRLF));
     assert_eq!(cfg.compact_balance_spacing, Some(true));
+    assert_eq!(cfg.minimal, Some(true));
+    assert_eq!(cfg.normalize_comment_metadata, Some(true));
+    assert_eq!(cfg.currency_column, Some(60));
+    assert_eq!(cfg.collapse_string_spaces, Some(true));
+    assert_eq!(cfg.align_keyword, Some(true));
+    assert_eq!(cfg.sort_by_date, Some(true));
+    assert_eq!(cfg.same_date_order, Some(SameDateOrder::TypePriority));
+    assert_eq!(cfg.trim_comment_trailing, Some(false));
+    assert_eq!(cfg.align_text_directives, Some(true));
+    assert_eq!(cfg.preserve_leading_blank_lines, Some(true));
+    assert_eq!(cfg.num_width, Some(12));
+    assert_eq!(cfg.account_amount_spacing, Some(4));
+    assert_eq!(cfg.long_currency, Some(LongCurrency::Shrink));
+    assert_eq!(cfg.number_currency_spacing, Some(2));
+    assert_eq!(cfg.preserve_posting_blank_lines, Some(true));
+    assert_eq!(cfg.currency_position, Some(CurrencyPosition::Before));
+    assert_eq!(cfg.normalize_include_paths, Some(true));
+    assert_eq!(cfg.prefix_width, Some(40));
+    assert_eq!(cfg.verify_semantics, Some(true));
+    assert_eq!(cfg.use_tabs, Some(true));
+    assert_eq!(cfg.global_account_alignment, Some(true));
+    assert_eq!(cfg.respect_column_hints, Some(true));
+    assert_eq!(cfg.metadata_extra_indent, Some(2));
+    assert_eq!(cfg.blank_lines, Some(BlankLines::Preserve));
+    assert_eq!(cfg.alignment_tolerance, Some(2));
+    assert_eq!(cfg.sort_tags_links, Some(true));
+    assert_eq!(cfg.tag_link_grouping, Some(TagLinkGrouping::LinksFirst));
+    assert_eq!(cfg.date_separator, Some(DateSeparator::Slash));
+    assert_eq!(cfg.sort_directives, Some(true));
+    assert_eq!(cfg.sort_postings, Some(true));
+    assert_eq!(cfg.max_blank_lines, Some(1));
+    assert_eq!(cfg.blank_lines_around_transactions, Some(false));
+    assert_eq!(cfg.align_custom, Some(true));
+    assert_eq!(cfg.comment_column, Some(60));
+    assert_eq!(cfg.comment_anchoring, Some(CommentAnchoring::Preceding));
+    assert_eq!(cfg.flag_in_margin, Some(true));
+    assert_eq!(
+      cfg.long_comment_placement,
+      Some(LongCommentPlacement::Above)
+    );
+    assert_eq!(cfg.normalize_metadata_dates, Some(true));
+    assert_eq!(cfg.price_operator_spacing, Some(0));
+    assert_eq!(cfg.minimize_diff, Some(true));
+    assert_eq!(cfg.normalize_currency_case, Some(true));
+    assert_eq!(cfg.thousands_separator, Some(ThousandsSeparator::Strip));
+    assert_eq!(cfg.trailing_newline, Some(false));
   }
   #[test]
   fn parses_partial_pyproject_tool_section() {
@@ -315,8 +1460,9 @@ mod tests {
 
     assert_eq!(cfg.line_width, Some(88));
     assert_eq!(cfg.indent_width, Some(3));
-    assert_eq!(cfg.new_line, None);
+    assert_eq!(cfg.new_line_kind, None);
     assert_eq!(cfg.compact_balance_spacing, None);
+    assert_eq!(cfg.minimal, None);
   }
 
   #[test]
@@ -340,23 +1486,174 @@ name = "example"
   #[test]
   fn overrides_take_precedence_when_both_set() {
     let pyproject_partial = Some(PyprojectPartialConfiguration {
+      exclude: None,
+      generated_marker: None,
       line_width: Some(70),
       indent_width: Some(2),
-      new_line: Some(NewLineKind::LF),
+      new_line_kind: Some(NewLineKind::LF),
       compact_balance_spacing: Some(false),
+      minimal: Some(false),
+      normalize_comment_metadata: Some(false),
+      currency_column: Some(50),
+      collapse_string_spaces: Some(false),
+      align_keyword: Some(false),
+      sort_by_date: Some(false),
+      same_date_order: Some(SameDateOrder::Source),
+      trim_comment_trailing: Some(true),
+      align_text_directives: Some(false),
+      preserve_leading_blank_lines: Some(false),
+      num_width: Some(8),
+      account_amount_spacing: Some(3),
+      long_currency: Some(LongCurrency::Shrink),
+      number_currency_spacing: Some(1),
+      preserve_posting_blank_lines: Some(false),
+      currency_position: Some(CurrencyPosition::Before),
+      normalize_include_paths: Some(false),
+      prefix_width: Some(30),
+      verify_semantics: Some(false),
+      use_tabs: Some(false),
+      global_account_alignment: Some(false),
+      respect_column_hints: Some(false),
+      metadata_extra_indent: Some(1),
+      blank_lines: Some(BlankLines::Normalize),
+      alignment_tolerance: Some(1),
+      sort_tags_links: Some(false),
+      tag_link_grouping: Some(TagLinkGrouping::LinksFirst),
+      date_separator: Some(DateSeparator::Dash),
+      sort_directives: Some(false),
+      sort_postings: Some(false),
+      max_blank_lines: Some(3),
+      blank_lines_around_transactions: Some(true),
+      align_custom: Some(false),
+      comment_column: Some(50),
+      comment_anchoring: Some(CommentAnchoring::Following),
+      flag_in_margin: Some(false),
+      long_comment_placement: Some(LongCommentPlacement::Below),
+      normalize_metadata_dates: Some(false),
+      price_operator_spacing: Some(1),
+      minimize_diff: Some(false),
+      normalize_currency_case: Some(false),
+      thousands_separator: Some(ThousandsSeparator::Comma),
+      trailing_newline: Some(true),
     });
     let overrides = CliPartialConfiguration {
       line_width: Some(88),
       indent_width: Some(4),
-      new_line: Some(NewLineKind::CRLF),
+      new_line_kind: Some(NewLineKind::CRLF),
       compact_balance_spacing: Some(true),
+      minimal: Some(true),
+      normalize_comment_metadata: Some(true),
+      currency_column: Some(60),
+      collapse_string_spaces: Some(true),
+      align_keyword: Some(true),
+      sort_by_date: Some(true),
+      same_date_order: None,
+      trim_comment_trailing: Some(false),
+      align_text_directives: Some(true),
+      preserve_leading_blank_lines: Some(true),
+      num_width: Some(10),
+      account_amount_spacing: Some(2),
+      long_currency: Some(LongCurrency::Overflow),
+      number_currency_spacing: Some(3),
+      preserve_posting_blank_lines: Some(true),
+      currency_position: Some(CurrencyPosition::After),
+      normalize_include_paths: Some(true),
+      prefix_width: Some(36),
+      verify_semantics: Some(true),
+      use_tabs: Some(true),
+      global_account_alignment: Some(true),
+      respect_column_hints: Some(true),
+      metadata_extra_indent: Some(4),
+      blank_lines: Some(BlankLines::Preserve),
+      alignment_tolerance: Some(3),
+      sort_tags_links: Some(true),
+      tag_link_grouping: Some(TagLinkGrouping::TagsFirst),
+      date_separator: Some(DateSeparator::Slash),
+      sort_directives: Some(true),
+      sort_postings: Some(true),
+      max_blank_lines: Some(0),
+      blank_lines_around_transactions: Some(false),
+      align_custom: Some(true),
+      comment_column: Some(40),
+      comment_anchoring: Some(CommentAnchoring::Preceding),
+      flag_in_margin: Some(true),
+      long_comment_placement: Some(LongCommentPlacement::Above),
+      normalize_metadata_dates: Some(true),
+      price_operator_spacing: Some(2),
+      minimize_diff: Some(true),
+      normalize_currency_case: Some(true),
+      thousands_separator: Some(ThousandsSeparator::Strip),
+      trailing_newline: Some(false),
     };
 
     let resolved = resolve_final_configuration(pyproject_partial, &overrides);
 
     assert_eq!(resolved.line_width, 88);
     assert_eq!(resolved.indent_width, 4);
-    assert_eq!(resolved.new_line, NewLineKind::CRLF);
+    assert_eq!(resolved.new_line_kind, NewLineKind::CRLF);
     assert!(resolved.compact_balance_spacing);
+    assert!(resolved.minimal);
+    assert!(resolved.normalize_comment_metadata);
+    assert_eq!(resolved.currency_column, Some(60));
+    assert!(resolved.collapse_string_spaces);
+    assert!(resolved.align_keyword);
+    assert!(resolved.sort_by_date);
+    assert_eq!(resolved.same_date_order, SameDateOrder::Source);
+    assert!(!resolved.trim_comment_trailing);
+    assert!(resolved.align_text_directives);
+    assert!(resolved.preserve_leading_blank_lines);
+    assert_eq!(resolved.num_width, Some(10));
+    assert_eq!(resolved.account_amount_spacing, Some(2));
+    assert_eq!(resolved.long_currency, LongCurrency::Overflow);
+    assert_eq!(resolved.number_currency_spacing, 3);
+    assert!(resolved.preserve_posting_blank_lines);
+    assert_eq!(resolved.currency_position, CurrencyPosition::After);
+    assert!(resolved.normalize_include_paths);
+    assert_eq!(resolved.prefix_width, Some(36));
+    assert!(resolved.verify_semantics);
+    assert!(resolved.use_tabs);
+    assert!(resolved.global_account_alignment);
+    assert!(resolved.respect_column_hints);
+    assert_eq!(resolved.metadata_extra_indent, 4);
+    assert_eq!(resolved.blank_lines, BlankLines::Preserve);
+    assert_eq!(resolved.alignment_tolerance, 3);
+    assert!(resolved.sort_tags_links);
+    assert_eq!(resolved.tag_link_grouping, TagLinkGrouping::TagsFirst);
+    assert_eq!(resolved.date_separator, DateSeparator::Slash);
+    assert!(resolved.sort_directives);
+    assert!(resolved.sort_postings);
+    assert_eq!(resolved.max_blank_lines, 0);
+    assert!(!resolved.blank_lines_around_transactions);
+    assert!(resolved.align_custom);
+    assert_eq!(resolved.comment_column, Some(40));
+    assert_eq!(resolved.comment_anchoring, CommentAnchoring::Preceding);
+    assert!(resolved.flag_in_margin);
+    assert_eq!(resolved.long_comment_placement, LongCommentPlacement::Above);
+    assert!(resolved.normalize_metadata_dates);
+    assert_eq!(resolved.price_operator_spacing, 2);
+    assert!(resolved.minimize_diff);
+    assert!(resolved.normalize_currency_case);
+    assert_eq!(resolved.thousands_separator, ThousandsSeparator::Strip);
+    assert!(!resolved.trailing_newline);
+  }
+
+  #[test]
+  fn pyproject_new_line_kind_reaches_format() {
+    let content = r#"
+[tool.beancount-format]
+new-line-kind = "crlf"
+"#;
+    let partial = parse_pyproject(content)
+      .expect("pyproject should parse")
+      .tool
+      .and_then(|tool| tool.beancount_formatter);
+
+    let resolved =
+      resolve_final_configuration(partial, &CliPartialConfiguration::default());
+
+    assert_eq!(resolved.new_line_kind, NewLineKind::CRLF);
+    let formatted =
+      format("2010-01-01 open Assets:Cash\n", &resolved).expect("format failed");
+    assert_eq!(formatted, "2010-01-01 open Assets:Cash\r\n");
   }
 }
@@ -5,6 +5,9 @@ use anyhow::Result;
 fn main() -> Result<()> {
   let outcome = beancount_formatter_cli::main_with_args(env::args_os())?;
 
+  if outcome.any_parse_error || outcome.any_failed {
+    process::exit(2);
+  }
   if outcome.any_changed {
     process::exit(1);
   }
@@ -6,6 +6,8 @@ use std::path::Path;
 
 const UNFORMATTED: &str = "2010-01-01 open\tAssets:Cash   \n";
 const FORMATTED: &str = "2010-01-01 open Assets:Cash\n";
+const BROKEN: &str = "2010-01-01 open Assets:Cash\n2010-01-02 budget Assets:Cash\n";
+const UNFORMATTABLE: &str = "2010-01-01 open Assets:Cash\n2010-01-06 price USD 10\n";
 const BALANCE_WITH_BLANKS: &str = "2000-01-01 open Assets:Cash\n\n2000-01-02 balance Assets:Cash 1 CNY\n\n\n2000-01-03 balance Assets:Cash 2 CNY\n";
 
 fn to_posix_path(path: &Path) -> String {
@@ -197,3 +199,818 @@ compact-balance-spacing = true
 
   Ok(())
 }
+
+fn git(dir: &Path, args: &[&str]) {
+  let status = std::process::Command::new("git")
+    .current_dir(dir)
+    .args([
+      "-c",
+      "user.name=test",
+      "-c",
+      "user.email=test@example.com",
+      "-c",
+      "commit.gpgsign=false",
+    ])
+    .args(args)
+    .status()
+    .expect("failed to run git");
+  assert!(status.success(), "git {args:?} failed");
+}
+
+#[test]
+fn changed_formats_only_files_modified_in_git() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let touched = temp.child("touched.bean");
+  let untouched = temp.child("untouched.bean");
+  touched.write_str(UNFORMATTED)?;
+  untouched.write_str(UNFORMATTED)?;
+
+  git(temp.path(), &["init", "-q"]);
+  git(temp.path(), &["add", "."]);
+  git(temp.path(), &["commit", "-q", "-m", "init"]);
+
+  touched.write_str(&format!("{UNFORMATTED}2010-01-02 close\tAssets:Cash\n"))?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(temp.path());
+  cmd.arg("--changed").arg(temp.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stdout(predicate::str::is_empty())
+    .stderr(
+      predicate::str::contains(format!(
+        "formatting: {}",
+        to_posix_path(touched.path())
+      ))
+      .and(predicate::str::contains("untouched.bean").not()),
+    );
+
+  touched.assert(eq(
+    "2010-01-01 open Assets:Cash\n2010-01-02 close Assets:Cash\n",
+  ));
+  untouched.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn changed_includes_untracked_files_from_another_directory() -> Result<()> {
+  let repo = assert_fs::TempDir::new()?;
+  let elsewhere = assert_fs::TempDir::new()?;
+  let committed = repo.child("ledgers/committed.bean");
+  committed.write_str(UNFORMATTED)?;
+
+  git(repo.path(), &["init", "-q"]);
+  git(repo.path(), &["add", "."]);
+  git(repo.path(), &["commit", "-q", "-m", "init"]);
+
+  let untracked = repo.child("ledgers/new.bean");
+  untracked.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(elsewhere.path());
+  cmd.arg("--changed").arg(repo.child("ledgers").path());
+
+  cmd.assert().failure().stderr(
+    predicate::str::contains(format!(
+      "formatting: {}",
+      to_posix_path(untracked.path())
+    ))
+    .and(predicate::str::contains("committed.bean").not()),
+  );
+
+  untracked.assert(eq(FORMATTED));
+  committed.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn changed_outside_git_repository_errors() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("ledger.bean");
+  file.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(temp.path());
+  // Keep git from discovering a repository above the temp dir.
+  if let Some(parent) = temp.path().parent() {
+    cmd.env("GIT_CEILING_DIRECTORIES", parent);
+  }
+  cmd.arg("--changed").arg(file.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("git repository"));
+
+  file.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn lint_reports_duplicate_metadata_without_writing() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("ledger.bean");
+  let content = "2010-01-01 open\tAssets:Cash\n  name: \"a\"\n  name: \"b\"\n";
+  file.write_str(content)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--lint").arg(file.path());
+
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::is_empty())
+    .stderr(predicate::str::contains(format!(
+      "{}:3:3: duplicate metadata key `name`",
+      to_posix_path(file.path())
+    )));
+
+  file.assert(eq(content));
+  Ok(())
+}
+
+#[test]
+fn check_mode_reports_parse_errors_and_keeps_going() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let broken = temp.child("broken.bean");
+  let unformatted = temp.child("unformatted.bean");
+  broken.write_str(BROKEN)?;
+  unformatted.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--check").arg(temp.path());
+
+  cmd
+    .assert()
+    .code(2)
+    .stdout(predicate::str::is_empty())
+    .stderr(
+      predicate::str::contains(format!(
+        "parse error: {}: 2:1:",
+        to_posix_path(broken.path())
+      ))
+      .and(predicate::str::contains(format!(
+        "checking failed: {}",
+        to_posix_path(unformatted.path())
+      ))),
+    );
+
+  unformatted.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn raw_entries_are_kept_when_formatting() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("raw.bean");
+  file.write_str("2010-01-01 open\tAssets:Cash   \n2010-01-02 budget Assets:Cash\n")?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg(file.path());
+
+  cmd
+    .assert()
+    .code(1)
+    .stderr(predicate::str::contains("parse error").not());
+
+  file.assert(eq(BROKEN));
+  Ok(())
+}
+
+#[test]
+fn broken_file_is_left_alone_and_the_rest_is_formatted() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let broken = temp.child("broken.bean");
+  let good = temp.child("good.bean");
+  broken.write_str(UNFORMATTABLE)?;
+  good.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg(temp.path());
+
+  cmd.assert().code(2).stderr(
+    predicate::str::contains(format!("error: {}:", to_posix_path(broken.path()))).and(
+      predicate::str::contains(format!("formatting: {}", to_posix_path(good.path()))),
+    ),
+  );
+
+  broken.assert(eq(UNFORMATTABLE));
+  good.assert(eq(FORMATTED));
+  Ok(())
+}
+
+#[test]
+fn fail_fast_stops_at_the_broken_file() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let broken = temp.child("broken.bean");
+  broken.write_str(UNFORMATTABLE)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--fail-fast").arg(temp.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("Failed to format"));
+
+  broken.assert(eq(UNFORMATTABLE));
+  Ok(())
+}
+
+#[test]
+fn fail_fast_leaves_later_files_unmodified() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let broken = temp.child("a_broken.bean");
+  let later = temp.child("b_later.bean");
+  broken.write_str(UNFORMATTABLE)?;
+  later.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.args(["--fail-fast", "--jobs", "4"]).arg(temp.path());
+
+  // The price without a currency really fails to format, and nothing runs
+  // after it.
+  cmd.assert().failure().stderr(
+    predicate::str::contains(format!(
+      "Failed to format {}",
+      to_posix_path(broken.path())
+    ))
+    .and(predicate::str::contains("formatting:").not()),
+  );
+
+  broken.assert(eq(UNFORMATTABLE));
+  later.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn fail_fast_check_stops_before_later_files() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let broken = temp.child("a_broken.bean");
+  let later = temp.child("b_later.bean");
+  broken.write_str(BROKEN)?;
+  later.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .args(["--check", "--fail-fast", "--jobs", "4"])
+    .arg(temp.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stdout(predicate::str::is_empty())
+    .stderr(
+      predicate::str::contains(format!(
+        "Failed to parse {}",
+        to_posix_path(broken.path())
+      ))
+      .and(predicate::str::contains("b_later.bean").not()),
+    );
+
+  later.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn stdin_check_reports_parse_errors_like_files() -> Result<()> {
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .args(["--check", "--stdin-filepath", "ledger.bean", "-"])
+    .write_stdin(BROKEN);
+
+  cmd
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("parse error: ledger.bean: 2:1"));
+  Ok(())
+}
+
+#[test]
+fn generated_files_are_skipped() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let generated = temp.child("generated.bean");
+  let content = format!("; AUTO-GENERATED, DO NOT EDIT\n{UNFORMATTED}");
+  generated.write_str(&content)?;
+  let handwritten = temp.child("handwritten.bean");
+  handwritten.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--generated-marker")
+    .arg("DO NOT EDIT")
+    .arg(temp.path());
+
+  cmd.assert().failure().stderr(
+    predicate::str::contains(format!(
+      "skipped generated file: {}",
+      to_posix_path(generated.path())
+    ))
+    .and(predicate::str::contains(format!(
+      "formatting: {}",
+      to_posix_path(handwritten.path())
+    ))),
+  );
+
+  assert_eq!(std::fs::read_to_string(generated.path())?, content);
+  handwritten.assert(eq(FORMATTED));
+  Ok(())
+}
+
+#[test]
+fn check_mode_leading_blank_line_depends_on_preserve_setting() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("leading.bean");
+  let content = format!("\n{FORMATTED}");
+  file.write_str(&content)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--check").arg(file.path());
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(format!(
+      "checking failed: {}",
+      to_posix_path(file.path())
+    )));
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--check")
+    .arg("--preserve-leading-blank-lines")
+    .arg(file.path());
+  cmd.assert().success().stderr(predicate::str::is_empty());
+
+  file.assert(eq(content.as_str()));
+  Ok(())
+}
+
+#[test]
+fn follow_includes_resolves_relative_to_including_file() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let main = temp.child("ledger/main.bean");
+  main.write_str("include \"accounts.bean\"\n")?;
+  let sibling = temp.child("ledger/accounts.bean");
+  sibling.write_str(UNFORMATTED)?;
+  let at_root = temp.child("accounts.bean");
+  at_root.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--follow-includes").arg(main.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(format!(
+      "formatting: {}",
+      to_posix_path(sibling.path())
+    )));
+
+  sibling.assert(eq(FORMATTED));
+  at_root.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn follow_includes_resolves_relative_to_include_root() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let main = temp.child("ledger/main.bean");
+  main.write_str("include \"accounts.bean\"\n")?;
+  let sibling = temp.child("ledger/accounts.bean");
+  sibling.write_str(UNFORMATTED)?;
+  let at_root = temp.child("accounts.bean");
+  at_root.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--follow-includes")
+    .arg("--include-root")
+    .arg(temp.path())
+    .arg(main.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(format!(
+      "formatting: {}",
+      to_posix_path(at_root.path())
+    )));
+
+  at_root.assert(eq(FORMATTED));
+  sibling.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn follow_includes_expands_glob_patterns() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let main = temp.child("main.bean");
+  main.write_str("include \"accounts/*.bean\"\n")?;
+  let cash = temp.child("accounts/cash.bean");
+  cash.write_str(UNFORMATTED)?;
+  let bank = temp.child("accounts/bank.bean");
+  bank.write_str(UNFORMATTED)?;
+  let nested = temp.child("accounts/old/archive.bean");
+  nested.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--follow-includes").arg(main.path());
+
+  cmd.assert().failure().stderr(
+    predicate::str::contains(format!("formatting: {}", to_posix_path(cash.path())))
+      .and(predicate::str::contains(format!(
+        "formatting: {}",
+        to_posix_path(bank.path())
+      ))),
+  );
+
+  cash.assert(eq(FORMATTED));
+  bank.assert(eq(FORMATTED));
+  nested.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn follow_includes_applies_excludes_and_extensions() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let main = temp.child("main.bean");
+  main.write_str(
+    "include \"kept.bean\"\ninclude \"vendor/prices.bean\"\ninclude \"notes.txt\"\n",
+  )?;
+  let kept = temp.child("kept.bean");
+  kept.write_str(UNFORMATTED)?;
+  let excluded = temp.child("vendor/prices.bean");
+  excluded.write_str(UNFORMATTED)?;
+  let other = temp.child("notes.txt");
+  other.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--follow-includes")
+    .arg("--exclude")
+    .arg("vendor/**")
+    .arg(main.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(format!(
+      "formatting: {}",
+      to_posix_path(kept.path())
+    )));
+
+  kept.assert(eq(FORMATTED));
+  excluded.assert(eq(UNFORMATTED));
+  other.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn lint_warns_about_long_metadata_values() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("ledger.bean");
+  file.write_str("2010-01-01 open Assets:Cash\n  note: \"a very long note\"\n")?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--lint")
+    .arg("--warn-long-metadata")
+    .arg("8")
+    .arg(file.path());
+
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains(format!(
+      "{}:2:3: metadata value of `note` is 18 characters long (limit 8)",
+      to_posix_path(file.path())
+    )));
+  Ok(())
+}
+
+#[test]
+fn stdin_is_formatted_to_stdout() -> Result<()> {
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("-").write_stdin(UNFORMATTED);
+
+  cmd
+    .assert()
+    .success()
+    .stdout(eq(FORMATTED))
+    .stderr(predicate::str::is_empty());
+  Ok(())
+}
+
+#[test]
+fn stdin_check_mode_fails_when_unformatted() -> Result<()> {
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .args(["--check", "--stdin-filepath", "ledger/main.bean", "-"])
+    .write_stdin(UNFORMATTED);
+
+  cmd
+    .assert()
+    .failure()
+    .stdout(predicate::str::is_empty())
+    .stderr(predicate::str::contains(
+      "checking failed: ledger/main.bean",
+    ));
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.args(["--check", "-"]).write_stdin(FORMATTED);
+  cmd.assert().success().stdout(predicate::str::is_empty());
+  Ok(())
+}
+
+#[test]
+fn stdin_filepath_locates_pyproject() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  temp.child("pyproject.toml").write_str(
+    r#"
+[tool.beancount-format]
+new-line-kind = "crlf"
+"#,
+  )?;
+  temp.child("ledger").create_dir_all()?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--stdin-filepath")
+    .arg(temp.child("ledger").child("main.bean").path())
+    .arg("-")
+    .write_stdin(FORMATTED);
+
+  cmd
+    .assert()
+    .success()
+    .stdout(eq("2010-01-01 open Assets:Cash\r\n"));
+  Ok(())
+}
+
+#[test]
+fn config_flag_reads_a_file_outside_the_ledger_tree() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  temp.child("ledger").child("pyproject.toml").write_str(
+    r#"
+[tool.beancount-format]
+new-line-kind = "crlf"
+"#,
+  )?;
+  let file = temp.child("ledger").child("main.bean");
+  file.write_str(FORMATTED)?;
+  let config = temp.child("shared").child("beancount-format.toml");
+  config.write_str("indent-width = 4\nnew-line-kind = \"lf\"\n")?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--config").arg(config.path()).arg(file.path());
+  cmd.assert().success();
+  file.assert(eq(FORMATTED));
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .arg("--config")
+    .arg(temp.child("missing.toml").path())
+    .arg(file.path());
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("does not exist"));
+  Ok(())
+}
+
+#[test]
+fn config_flag_rejects_unknown_options() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("main.bean");
+  file.write_str(UNFORMATTED)?;
+  let config = temp.child("config.toml");
+  config.write_str("line-widht = 80\n")?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--config").arg(config.path()).arg(file.path());
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("line-widht"));
+  file.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn standalone_config_file_is_discovered() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  temp
+    .child(".beancount-format.toml")
+    .write_str("new-line-kind = \"crlf\"\n")?;
+  let file = temp.child("ledger").child("main.bean");
+  file.write_str(FORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(temp.path()).arg(file.path());
+  cmd.assert().failure();
+  file.assert(eq("2010-01-01 open Assets:Cash\r\n"));
+  Ok(())
+}
+
+#[test]
+fn config_precedence_is_flag_then_standalone_then_pyproject() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  temp.child("pyproject.toml").write_str(
+    r#"
+[tool.beancount-format]
+new-line-kind = "crlf"
+"#,
+  )?;
+  temp
+    .child("beancount-format.toml")
+    .write_str("new-line-kind = \"lf\"\n")?;
+  let file = temp.child("main.bean");
+  file.write_str(FORMATTED)?;
+
+  // The standalone file beats pyproject.toml in the same directory.
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(temp.path()).arg(file.path());
+  cmd.assert().success();
+  file.assert(eq(FORMATTED));
+
+  // --config beats both.
+  let config = temp.child("other").child("config.toml");
+  config.write_str("new-line-kind = \"crlf\"\n")?;
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .current_dir(temp.path())
+    .arg("--config")
+    .arg(config.path())
+    .arg(file.path());
+  cmd.assert().failure();
+  file.assert(eq("2010-01-01 open Assets:Cash\r\n"));
+  Ok(())
+}
+
+#[test]
+fn quiet_mode_prints_nothing() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("needs-format.bean");
+  file.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--quiet").arg(file.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stdout(predicate::str::is_empty())
+    .stderr(predicate::str::is_empty());
+  file.assert(eq(FORMATTED));
+  Ok(())
+}
+
+#[test]
+fn check_mode_prints_json_report() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let formatted = temp.child("a.bean");
+  formatted.write_str(FORMATTED)?;
+  let unformatted = temp.child("b.bean");
+  unformatted.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .args(["--check", "--quiet", "--format", "json"])
+    .arg(formatted.path())
+    .arg(unformatted.path());
+
+  let output = cmd.assert().failure().get_output().stdout.clone();
+  let report: serde_json::Value = serde_json::from_slice(&output)?;
+  assert_eq!(
+    report,
+    serde_json::json!([
+      { "path": to_posix_path(formatted.path()), "changed": false },
+      { "path": to_posix_path(unformatted.path()), "changed": true },
+    ])
+  );
+  unformatted.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn diff_mode_prints_unified_diff_without_writing() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("needs-format.bean");
+  file.write_str(UNFORMATTED)?;
+  let path = to_posix_path(file.path());
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--diff").arg(file.path());
+
+  cmd
+    .assert()
+    .failure()
+    .stdout(
+      predicate::str::contains(format!("--- {path}"))
+        .and(predicate::str::contains(format!("+++ {path}")))
+        .and(predicate::str::contains("@@ -1"))
+        .and(predicate::str::contains(
+          "-2010-01-01 open\tAssets:Cash   \n",
+        ))
+        .and(predicate::str::contains("+2010-01-01 open Assets:Cash\n")),
+    )
+    .stderr(predicate::str::is_empty());
+
+  file.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn diff_mode_is_silent_for_formatted_files() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let file = temp.child("already.bean");
+  file.write_str(FORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.arg("--diff").arg(file.path());
+
+  cmd.assert().success().stdout(predicate::str::is_empty());
+  Ok(())
+}
+
+#[test]
+fn directory_walk_skips_gitignored_files() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  temp.child(".gitignore").write_str("build/\n")?;
+  let kept = temp.child("main.bean");
+  kept.write_str(UNFORMATTED)?;
+  let ignored = temp.child("build").child("generated.bean");
+  ignored.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(temp.path()).arg("--check").arg(temp.path());
+
+  cmd.assert().failure().stderr(
+    predicate::str::contains("main.bean")
+      .and(predicate::str::contains("generated.bean").not()),
+  );
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.current_dir(temp.path()).arg(temp.path());
+  cmd.assert().failure();
+
+  kept.assert(eq(FORMATTED));
+  ignored.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn exclude_globs_from_cli_and_pyproject_are_skipped() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  temp.child("pyproject.toml").write_str(
+    r#"
+[tool.beancount-format]
+exclude = ["vendor/**"]
+"#,
+  )?;
+  let kept = temp.child("main.bean");
+  kept.write_str(UNFORMATTED)?;
+  let vendored = temp.child("vendor").child("other.bean");
+  vendored.write_str(UNFORMATTED)?;
+  let generated = temp.child("generated.bean");
+  generated.write_str(UNFORMATTED)?;
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd
+    .current_dir(temp.path())
+    .args(["--exclude", "generated.bean"])
+    .arg(temp.path());
+  cmd.assert().failure();
+
+  kept.assert(eq(FORMATTED));
+  vendored.assert(eq(UNFORMATTED));
+  generated.assert(eq(UNFORMATTED));
+  Ok(())
+}
+
+#[test]
+fn formats_many_files_in_parallel_with_sorted_messages() -> Result<()> {
+  let temp = assert_fs::TempDir::new()?;
+  let files: Vec<_> = (0..40)
+    .map(|idx| temp.child(format!("ledger-{idx:02}.bean")))
+    .collect();
+  for file in &files {
+    file.write_str(UNFORMATTED)?;
+  }
+
+  let mut cmd: Command = cargo_bin_cmd!("beancount-format");
+  cmd.args(["--jobs", "4"]).arg(temp.path());
+  let output = cmd.assert().failure().get_output().clone();
+
+  let expected: String = files
+    .iter()
+    .map(|file| format!("formatting: {}\n", to_posix_path(file.path())))
+    .collect();
+  assert_eq!(String::from_utf8(output.stderr)?, expected);
+  for file in &files {
+    file.assert(eq(FORMATTED));
+  }
+  Ok(())
+}
@@ -1,48 +1,263 @@
-use beancount_formatter::configuration::{NewLineKind, PartialConfiguration};
+use beancount_formatter::configuration::{
+  BlankLines, CommentAnchoring, CurrencyPosition, DateSeparator, LongCommentPlacement,
+  LongCurrency, NewLineKind, PartialConfiguration, SameDateOrder, TagLinkGrouping,
+  ThousandsSeparator,
+};
 use beancount_formatter::format;
 use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use std::path::PathBuf;
 
+#[allow(clippy::too_many_arguments)]
 #[pyfunction(name = "format_text")]
 #[pyo3(signature = (
   text,
   *,
+  filename = None,
   line_width = None,
   indent_width = None,
   new_line = None,
-  compact_balance_spacing = None
+  compact_balance_spacing = None,
+  minimal = None,
+  normalize_comment_metadata = None,
+  currency_column = None,
+  collapse_string_spaces = None,
+  align_keyword = None,
+  sort_by_date = None,
+  same_date_order = None,
+  trim_comment_trailing = None,
+  align_text_directives = None,
+  preserve_leading_blank_lines = None,
+  num_width = None,
+  account_amount_spacing = None,
+  long_currency = None,
+  number_currency_spacing = None,
+  preserve_posting_blank_lines = None,
+  currency_position = None,
+  normalize_include_paths = None,
+  prefix_width = None,
+  verify_semantics = None,
+  use_tabs = None,
+  global_account_alignment = None,
+  respect_column_hints = None,
+  metadata_extra_indent = None,
+  blank_lines = None,
+  alignment_tolerance = None,
+  sort_tags_links = None,
+  tag_link_grouping = None,
+  date_separator = None,
+  sort_directives = None,
+  sort_postings = None,
+  max_blank_lines = None,
+  blank_lines_around_transactions = None,
+  align_custom = None,
+  comment_column = None,
+  comment_anchoring = None,
+  flag_in_margin = None,
+  long_comment_placement = None,
+  normalize_metadata_dates = None,
+  price_operator_spacing = None,
+  minimize_diff = None,
+  normalize_currency_case = None,
+  thousands_separator = None,
+  trailing_newline = None
 ))]
 fn format_text_py(
   text: &str,
+  filename: Option<PathBuf>,
   line_width: Option<u32>,
   indent_width: Option<u8>,
   new_line: Option<&str>,
   compact_balance_spacing: Option<bool>,
+  minimal: Option<bool>,
+  normalize_comment_metadata: Option<bool>,
+  currency_column: Option<i64>,
+  collapse_string_spaces: Option<bool>,
+  align_keyword: Option<bool>,
+  sort_by_date: Option<bool>,
+  same_date_order: Option<&str>,
+  trim_comment_trailing: Option<bool>,
+  align_text_directives: Option<bool>,
+  preserve_leading_blank_lines: Option<bool>,
+  num_width: Option<i64>,
+  account_amount_spacing: Option<i64>,
+  long_currency: Option<&str>,
+  number_currency_spacing: Option<i64>,
+  preserve_posting_blank_lines: Option<bool>,
+  currency_position: Option<&str>,
+  normalize_include_paths: Option<bool>,
+  prefix_width: Option<i64>,
+  verify_semantics: Option<bool>,
+  use_tabs: Option<bool>,
+  global_account_alignment: Option<bool>,
+  respect_column_hints: Option<bool>,
+  metadata_extra_indent: Option<u8>,
+  blank_lines: Option<&str>,
+  alignment_tolerance: Option<u32>,
+  sort_tags_links: Option<bool>,
+  tag_link_grouping: Option<&str>,
+  date_separator: Option<&str>,
+  sort_directives: Option<bool>,
+  sort_postings: Option<bool>,
+  max_blank_lines: Option<u32>,
+  blank_lines_around_transactions: Option<bool>,
+  align_custom: Option<bool>,
+  comment_column: Option<u32>,
+  comment_anchoring: Option<&str>,
+  flag_in_margin: Option<bool>,
+  long_comment_placement: Option<&str>,
+  normalize_metadata_dates: Option<bool>,
+  price_operator_spacing: Option<u8>,
+  minimize_diff: Option<bool>,
+  normalize_currency_case: Option<bool>,
+  thousands_separator: Option<&str>,
+  trailing_newline: Option<bool>,
 ) -> PyResult<String> {
+  let currency_column = non_negative("currency_column", currency_column)?;
+  let num_width = non_negative("num_width", num_width)?;
+  let account_amount_spacing =
+    non_negative("account_amount_spacing", account_amount_spacing)?;
+  let number_currency_spacing =
+    non_negative("number_currency_spacing", number_currency_spacing)?;
+  let prefix_width = non_negative("prefix_width", prefix_width)?;
+
   let new_line_opt: Option<NewLineKind> = match new_line {
     Some(value) => Some(NewLineKind::parse(value).map_err(PyValueError::new_err)?),
     None => None,
   };
 
+  let same_date_order: Option<SameDateOrder> = match same_date_order {
+    Some(value) => Some(SameDateOrder::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let long_currency: Option<LongCurrency> = match long_currency {
+    Some(value) => Some(LongCurrency::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let currency_position: Option<CurrencyPosition> = match currency_position {
+    Some(value) => Some(CurrencyPosition::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let blank_lines: Option<BlankLines> = match blank_lines {
+    Some(value) => Some(BlankLines::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let tag_link_grouping: Option<TagLinkGrouping> = match tag_link_grouping {
+    Some(value) => Some(TagLinkGrouping::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let date_separator: Option<DateSeparator> = match date_separator {
+    Some(value) => Some(DateSeparator::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let comment_anchoring: Option<CommentAnchoring> = match comment_anchoring {
+    Some(value) => Some(CommentAnchoring::parse(value).map_err(PyValueError::new_err)?),
+    None => None,
+  };
+
+  let long_comment_placement: Option<LongCommentPlacement> =
+    match long_comment_placement {
+      Some(value) => {
+        Some(LongCommentPlacement::parse(value).map_err(PyValueError::new_err)?)
+      }
+      None => None,
+    };
+
+  let thousands_separator: Option<ThousandsSeparator> = match thousands_separator {
+    Some(value) => {
+      Some(ThousandsSeparator::parse(value).map_err(PyValueError::new_err)?)
+    }
+    None => None,
+  };
+
   let config = PartialConfiguration {
     line_width,
     indent_width,
-    new_line: new_line_opt,
+    new_line_kind: new_line_opt,
     compact_balance_spacing,
+    minimal,
+    normalize_comment_metadata,
+    currency_column,
+    collapse_string_spaces,
+    align_keyword,
+    sort_by_date,
+    same_date_order,
+    trim_comment_trailing,
+    align_text_directives,
+    preserve_leading_blank_lines,
+    num_width,
+    account_amount_spacing,
+    long_currency,
+    number_currency_spacing,
+    preserve_posting_blank_lines,
+    currency_position,
+    normalize_include_paths,
+    prefix_width,
+    verify_semantics,
+    use_tabs,
+    global_account_alignment,
+    respect_column_hints,
+    metadata_extra_indent,
+    blank_lines,
+    alignment_tolerance,
+    sort_tags_links,
+    tag_link_grouping,
+    date_separator,
+    sort_directives,
+    sort_postings,
+    max_blank_lines,
+    blank_lines_around_transactions,
+    align_custom,
+    comment_column,
+    comment_anchoring,
+    flag_in_margin,
+    long_comment_placement,
+    normalize_metadata_dates,
+    price_operator_spacing,
+    minimize_diff,
+    normalize_currency_case,
+    thousands_separator,
+    trailing_newline,
   }
   .resolve();
 
-  let formatted =
-    format(text, &config).map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+  // Errors name `filename`, or `example.beancount` when the caller gave none.
+  let path = filename.unwrap_or_else(|| PathBuf::from("example.beancount"));
+  let formatted = format(text, &config).map_err(|err| {
+    PyRuntimeError::new_err(format!("Failed to format {}: {err:#}", path.display()))
+  })?;
   Ok(formatted)
 }
 
+/// `value` as a column or space count, or a `ValueError` naming the option when
+/// it is negative or too large.
+fn non_negative<T: TryFrom<i64>>(
+  name: &str,
+  value: Option<i64>,
+) -> PyResult<Option<T>> {
+  value
+    .map(|value| {
+      T::try_from(value).map_err(|_| {
+        PyValueError::new_err(format!(
+          "{name} must be a non-negative integer that fits the option, got {value}"
+        ))
+      })
+    })
+    .transpose()
+}
+
 #[pyfunction(name = "main")]
 fn main_py(args: Vec<String>) -> PyResult<bool> {
   let outcome = beancount_formatter_cli::main_with_args(args)
     .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
 
-  Ok(outcome.any_changed)
+  Ok(outcome.any_changed || outcome.any_parse_error || outcome.any_failed)
 }
 
 #[pymodule]
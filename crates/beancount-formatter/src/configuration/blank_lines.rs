@@ -0,0 +1,50 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// How blank lines between directives are derived from the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlankLines {
+  /// Keep the source's blank lines (at most `max_blank_lines`), but put at
+  /// least one blank line around transactions.
+  #[serde(rename = "normalize")]
+  Normalize,
+  /// Keep the source's blank lines (at most `max_blank_lines`) exactly,
+  /// including none between adjacent transactions.
+  #[serde(rename = "preserve")]
+  Preserve,
+}
+
+impl BlankLines {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      BlankLines::Normalize => "normalize",
+      BlankLines::Preserve => "preserve",
+    }
+  }
+
+  /// Parse a blank line mode from a string. Accepts case-insensitive
+  /// "normalize" or "preserve".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "normalize" => Ok(BlankLines::Normalize),
+      "preserve" => Ok(BlankLines::Preserve),
+      other => Err(format!("Unsupported blank_lines: {}", other)),
+    }
+  }
+}
+
+impl Display for BlankLines {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for BlankLines {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
@@ -0,0 +1,48 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Which directive a comment line sticks to when sorting reorders directives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommentAnchoring {
+  /// Comments directly above a directive move with it.
+  #[serde(rename = "following")]
+  Following,
+  /// Comments directly below a directive move with it.
+  #[serde(rename = "preceding")]
+  Preceding,
+}
+
+impl CommentAnchoring {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CommentAnchoring::Following => "following",
+      CommentAnchoring::Preceding => "preceding",
+    }
+  }
+
+  /// Parse an anchoring from a string. Accepts case-insensitive "following" or
+  /// "preceding".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "following" => Ok(CommentAnchoring::Following),
+      "preceding" => Ok(CommentAnchoring::Preceding),
+      other => Err(format!("Unsupported comment_anchoring: {}", other)),
+    }
+  }
+}
+
+impl Display for CommentAnchoring {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for CommentAnchoring {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
@@ -1,25 +1,258 @@
-use super::NewLineKind;
+use super::{
+  BlankLines, CommentAnchoring, CurrencyPosition, DateSeparator, LongCommentPlacement,
+  LongCurrency, NewLineKind, SameDateOrder, TagLinkGrouping, ThousandsSeparator,
+};
 use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
 
 pub const DEFAULT_LINE_WIDTH: u32 = 70;
 pub const DEFAULT_INDENT_WIDTH: u8 = 2;
 pub const DEFAULT_NEW_LINE_KIND: NewLineKind = NewLineKind::LF;
 pub const DEFAULT_COMPACT_BALANCE_SPACING: bool = false;
+pub const DEFAULT_MINIMAL: bool = false;
+pub const DEFAULT_NORMALIZE_COMMENT_METADATA: bool = false;
+pub const DEFAULT_COLLAPSE_STRING_SPACES: bool = false;
+pub const DEFAULT_ALIGN_KEYWORD: bool = false;
+pub const DEFAULT_SORT_BY_DATE: bool = false;
+pub const DEFAULT_SAME_DATE_ORDER: SameDateOrder = SameDateOrder::Source;
+pub const DEFAULT_TRIM_COMMENT_TRAILING: bool = true;
+pub const DEFAULT_ALIGN_TEXT_DIRECTIVES: bool = false;
+pub const DEFAULT_PRESERVE_LEADING_BLANK_LINES: bool = false;
+pub const DEFAULT_LONG_CURRENCY: LongCurrency = LongCurrency::Overflow;
+pub const DEFAULT_NUMBER_CURRENCY_SPACING: u8 = 1;
+pub const DEFAULT_PRESERVE_POSTING_BLANK_LINES: bool = false;
+pub const DEFAULT_CURRENCY_POSITION: CurrencyPosition = CurrencyPosition::After;
+pub const DEFAULT_NORMALIZE_INCLUDE_PATHS: bool = false;
+pub const DEFAULT_VERIFY_SEMANTICS: bool = false;
+pub const DEFAULT_USE_TABS: bool = false;
+pub const DEFAULT_GLOBAL_ACCOUNT_ALIGNMENT: bool = false;
+pub const DEFAULT_RESPECT_COLUMN_HINTS: bool = false;
+pub const DEFAULT_METADATA_EXTRA_INDENT: u8 = 0;
+pub const DEFAULT_BLANK_LINES: BlankLines = BlankLines::Normalize;
+pub const DEFAULT_ALIGNMENT_TOLERANCE: u32 = 0;
+pub const DEFAULT_SORT_TAGS_LINKS: bool = false;
+pub const DEFAULT_TAG_LINK_GROUPING: TagLinkGrouping = TagLinkGrouping::Source;
+pub const DEFAULT_DATE_SEPARATOR: DateSeparator = DateSeparator::Dash;
+pub const DEFAULT_SORT_DIRECTIVES: bool = false;
+pub const DEFAULT_SORT_POSTINGS: bool = false;
+pub const DEFAULT_MAX_BLANK_LINES: u32 = 2;
+pub const DEFAULT_BLANK_LINES_AROUND_TRANSACTIONS: bool = true;
+pub const DEFAULT_ALIGN_CUSTOM: bool = false;
+pub const DEFAULT_COMMENT_ANCHORING: CommentAnchoring = CommentAnchoring::Following;
+pub const DEFAULT_FLAG_IN_MARGIN: bool = false;
+pub const DEFAULT_LONG_COMMENT_PLACEMENT: LongCommentPlacement =
+  LongCommentPlacement::Trailing;
+pub const DEFAULT_NORMALIZE_METADATA_DATES: bool = false;
+pub const DEFAULT_PRICE_OPERATOR_SPACING: u8 = 1;
+pub const DEFAULT_MINIMIZE_DIFF: bool = false;
+pub const DEFAULT_NORMALIZE_CURRENCY_CASE: bool = false;
+pub const DEFAULT_THOUSANDS_SEPARATOR: ThousandsSeparator =
+  ThousandsSeparator::Preserve;
+pub const DEFAULT_TRAILING_NEWLINE: bool = true;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Configuration {
   pub line_width: u32,
   pub indent_width: u8,
-  pub new_line: NewLineKind,
+  #[serde(alias = "new_line")]
+  pub new_line_kind: NewLineKind,
   pub compact_balance_spacing: bool,
+  /// Only normalize whitespace (tabs, trailing spaces, newlines and blank lines),
+  /// keeping every directive's columns and token spacing as written.
+  pub minimal: bool,
+  /// Normalize trailing comments shaped like `key: value` to a single space
+  /// after the colon.
+  pub normalize_comment_metadata: bool,
+  /// Align the currencies of posting, `balance` and `price` amounts to start at
+  /// this 1-based column. The column counts from the start of the line,
+  /// including indentation and posting flags. Lines too long for the column
+  /// keep a single space before the amount.
+  pub currency_column: Option<u32>,
+  /// Collapse runs of spaces inside payee and narration strings. This changes
+  /// string content, so it is off by default.
+  pub collapse_string_spaces: bool,
+  /// Align the operands of single-operand directives (`commodity`, `pushtag`,
+  /// `poptag`, `popmeta`) into one column across consecutive directives.
+  pub align_keyword: bool,
+  /// Reorder dated directives by date. Sorting is stable and never moves a
+  /// directive across an undated directive (option, pushtag, ...) or a standalone
+  /// comment; comments directly above a directive move with it.
+  pub sort_by_date: bool,
+  /// How `sort_by_date` orders directives sharing a date.
+  pub same_date_order: SameDateOrder,
+  /// Trim trailing whitespace inside comments. Disable to keep intentional
+  /// trailing spaces, e.g. in ASCII tables drawn in comment blocks.
+  pub trim_comment_trailing: bool,
+  /// Align the trailing string of text directives into one column across
+  /// consecutive directives of the same kind, e.g. `event` descriptions after the
+  /// widest event type.
+  pub align_text_directives: bool,
+  /// Keep blank lines before the first directive (at most `max_blank_lines`,
+  /// like blank lines between directives) instead of removing them.
+  pub preserve_leading_blank_lines: bool,
+  /// Pad the number of every amount on the left to this many characters
+  /// before its currency, so amounts form a fixed-width field. Longer numbers
+  /// are kept whole.
+  pub num_width: Option<u32>,
+  /// Put every posting amount exactly this many spaces after its own account,
+  /// leaving amounts ragged. Disables all amount alignment, including
+  /// `currency_column`, `prefix_width` and `global_account_alignment`.
+  pub account_amount_spacing: Option<u32>,
+  /// What `currency_column` does with an amount that would run past
+  /// `line_width`: keep the column and overflow, or shrink the padding.
+  pub long_currency: LongCurrency,
+  /// Number of spaces between the number of an amount and its currency.
+  pub number_currency_spacing: u8,
+  /// Keep blank lines inside a transaction, e.g. between a posting's metadata
+  /// and the next posting, instead of removing them.
+  pub preserve_posting_blank_lines: bool,
+  /// Write currencies after (beancount) or before (export-only) the number.
+  pub currency_position: CurrencyPosition,
+  /// Rewrite backslashes in `include` filenames to forward slashes so the
+  /// ledger works across platforms.
+  pub normalize_include_paths: bool,
+  /// Pad the part of each posting before its amount (indent, flag and
+  /// account) to this many columns, so amounts start at the same column across
+  /// the whole file. `currency_column` and `account_amount_spacing` win.
+  pub prefix_width: Option<u32>,
+  /// Re-parse the formatted output and fail if its directives differ from
+  /// the input's, ignoring whitespace and comments.
+  pub verify_semantics: bool,
+  /// Indent postings and metadata with one tab per indent level instead of
+  /// `indent_width` spaces. Comments and raw entries keep their indentation.
+  pub use_tabs: bool,
+  /// Start posting amounts at one column derived from the longest posting
+  /// account in the whole file, two spaces after it. `currency_column`,
+  /// `prefix_width` and `account_amount_spacing` take precedence.
+  pub global_account_alignment: bool,
+  /// Treat a `; col:N` comment line directly above a directive as its
+  /// `currency_column`, overriding the configured one for that directive only.
+  pub respect_column_hints: bool,
+  /// Extra spaces added to the indent of metadata lines. When non-zero, directive
+  /// metadata is re-indented to `indent_width + metadata_extra_indent` and posting
+  /// metadata to the posting indent plus that; `0` keeps the source indentation.
+  pub metadata_extra_indent: u8,
+  /// Whether blank lines between directives get at least one around
+  /// transactions (`normalize`) or are kept exactly as written (`preserve`),
+  /// at most `max_blank_lines` either way.
+  pub blank_lines: BlankLines,
+  /// Keep a posting amount where it is written when it starts within this many
+  /// columns of where alignment would put it, to avoid one-space diffs.
+  pub alignment_tolerance: u32,
+  /// Sort the `#tags` and `^links` after a header alphabetically, tags before
+  /// links, and drop exact repeats. With `tag_link_grouping`, each group is
+  /// sorted on its own.
+  pub sort_tags_links: bool,
+  /// Group the `#tags` and `^links` after a header by kind. Grouping happens
+  /// before `sort_tags_links` sorts within each group.
+  pub tag_link_grouping: TagLinkGrouping,
+  /// Separator written between the year, month and day of every date. Dates
+  /// that are not well-formed `YYYY-MM-DD`, `YYYY/MM/DD` or `YYYY.MM.DD` are
+  /// left as written.
+  pub date_separator: DateSeparator,
+  /// Like `sort_by_date`, but first moves `option`, `plugin` and `include`
+  /// directives to the top of the file in source order, so they no longer split
+  /// the file into separately sorted sections. It implies `sort_by_date`, which
+  /// makes no difference when both are set.
+  pub sort_directives: bool,
+  /// Reorder the postings of each transaction by account name. Sorting is
+  /// stable, and each posting keeps the metadata and comment lines below it.
+  pub sort_postings: bool,
+  /// Most blank lines kept between directives (and at the start of the file
+  /// with `preserve_leading_blank_lines`).
+  pub max_blank_lines: u32,
+  /// With `blank_lines` set to `normalize`, put at least one blank line
+  /// between a transaction and the directives around it.
+  pub blank_lines_around_transactions: bool,
+  /// Align the values of consecutive `custom` directives into one column after
+  /// the widest name.
+  pub align_custom: bool,
+  /// Start every inline `;` comment at this 1-based column, on directive and
+  /// posting lines alike. Lines already reaching it get a single space.
+  pub comment_column: Option<u32>,
+  /// Whether comment lines sorted with `sort_by_date` or `sort_directives` move
+  /// with the directive directly below (`following`) or above (`preceding`).
+  pub comment_anchoring: CommentAnchoring,
+  /// Write posting flags inside the indentation, so flagged and unflagged
+  /// postings start their accounts at the same column.
+  pub flag_in_margin: bool,
+  /// Where an inline comment goes when the line it is on, with a single space
+  /// before the comment, would be longer than `line_width`: kept `trailing`, or
+  /// moved to its own line `above` or `below` at the line's indent.
+  pub long_comment_placement: LongCommentPlacement,
+  /// Also write date values of metadata and `custom` directives with
+  /// `date_separator`. Only values that are a whole `YYYY-MM-DD`, `YYYY/MM/DD`
+  /// or `YYYY.MM.DD` date are rewritten; strings and other values are kept.
+  pub normalize_metadata_dates: bool,
+  /// Number of spaces between a posting's price operator, `@` or `@@`, and the
+  /// price that follows it. `0` writes `@@1100 USD`.
+  pub price_operator_spacing: u8,
+  /// Keep a directive as written, apart from tab expansion and trailing
+  /// whitespace, when formatting would only change the whitespace between its
+  /// tokens. Directives whose text changes otherwise are formatted in full.
+  pub minimize_diff: bool,
+  /// Uppercase currencies of `open`, `commodity` and `price` directives and of
+  /// amounts. A currency that still isn't a valid currency once uppercased is
+  /// kept as written.
+  pub normalize_currency_case: bool,
+  /// How `,` thousands separators in numbers of amounts, costs, prices and
+  /// balances are written: kept as written (`preserve`), removed (`none`) or
+  /// inserted every three integer digits (`comma`).
+  pub thousands_separator: ThousandsSeparator,
+  /// End the output with a newline. Turn off to embed formatted text elsewhere;
+  /// only the final newline is dropped.
+  pub trailing_newline: bool,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct PartialConfiguration {
   pub line_width: Option<u32>,
   pub indent_width: Option<u8>,
-  pub new_line: Option<NewLineKind>,
+  pub new_line_kind: Option<NewLineKind>,
   pub compact_balance_spacing: Option<bool>,
+  pub minimal: Option<bool>,
+  pub normalize_comment_metadata: Option<bool>,
+  pub currency_column: Option<u32>,
+  pub collapse_string_spaces: Option<bool>,
+  pub align_keyword: Option<bool>,
+  pub sort_by_date: Option<bool>,
+  pub same_date_order: Option<SameDateOrder>,
+  pub trim_comment_trailing: Option<bool>,
+  pub align_text_directives: Option<bool>,
+  pub preserve_leading_blank_lines: Option<bool>,
+  pub num_width: Option<u32>,
+  pub account_amount_spacing: Option<u32>,
+  pub long_currency: Option<LongCurrency>,
+  pub number_currency_spacing: Option<u8>,
+  pub preserve_posting_blank_lines: Option<bool>,
+  pub currency_position: Option<CurrencyPosition>,
+  pub normalize_include_paths: Option<bool>,
+  pub prefix_width: Option<u32>,
+  pub verify_semantics: Option<bool>,
+  pub use_tabs: Option<bool>,
+  pub global_account_alignment: Option<bool>,
+  pub respect_column_hints: Option<bool>,
+  pub metadata_extra_indent: Option<u8>,
+  pub blank_lines: Option<BlankLines>,
+  pub alignment_tolerance: Option<u32>,
+  pub sort_tags_links: Option<bool>,
+  pub tag_link_grouping: Option<TagLinkGrouping>,
+  pub date_separator: Option<DateSeparator>,
+  pub sort_directives: Option<bool>,
+  pub sort_postings: Option<bool>,
+  pub max_blank_lines: Option<u32>,
+  pub blank_lines_around_transactions: Option<bool>,
+  pub align_custom: Option<bool>,
+  pub comment_column: Option<u32>,
+  pub comment_anchoring: Option<CommentAnchoring>,
+  pub flag_in_margin: Option<bool>,
+  pub long_comment_placement: Option<LongCommentPlacement>,
+  pub normalize_metadata_dates: Option<bool>,
+  pub price_operator_spacing: Option<u8>,
+  pub minimize_diff: Option<bool>,
+  pub normalize_currency_case: Option<bool>,
+  pub thousands_separator: Option<ThousandsSeparator>,
+  pub trailing_newline: Option<bool>,
 }
 
 impl PartialConfiguration {
@@ -27,10 +260,89 @@ impl PartialConfiguration {
     Configuration {
       line_width: self.line_width.unwrap_or(DEFAULT_LINE_WIDTH),
       indent_width: self.indent_width.unwrap_or(DEFAULT_INDENT_WIDTH),
-      new_line: self.new_line.unwrap_or(DEFAULT_NEW_LINE_KIND),
+      new_line_kind: self.new_line_kind.unwrap_or(DEFAULT_NEW_LINE_KIND),
       compact_balance_spacing: self
         .compact_balance_spacing
         .unwrap_or(DEFAULT_COMPACT_BALANCE_SPACING),
+      minimal: self.minimal.unwrap_or(DEFAULT_MINIMAL),
+      normalize_comment_metadata: self
+        .normalize_comment_metadata
+        .unwrap_or(DEFAULT_NORMALIZE_COMMENT_METADATA),
+      currency_column: self.currency_column,
+      collapse_string_spaces: self
+        .collapse_string_spaces
+        .unwrap_or(DEFAULT_COLLAPSE_STRING_SPACES),
+      align_keyword: self.align_keyword.unwrap_or(DEFAULT_ALIGN_KEYWORD),
+      sort_by_date: self.sort_by_date.unwrap_or(DEFAULT_SORT_BY_DATE),
+      same_date_order: self.same_date_order.unwrap_or(DEFAULT_SAME_DATE_ORDER),
+      trim_comment_trailing: self
+        .trim_comment_trailing
+        .unwrap_or(DEFAULT_TRIM_COMMENT_TRAILING),
+      align_text_directives: self
+        .align_text_directives
+        .unwrap_or(DEFAULT_ALIGN_TEXT_DIRECTIVES),
+      preserve_leading_blank_lines: self
+        .preserve_leading_blank_lines
+        .unwrap_or(DEFAULT_PRESERVE_LEADING_BLANK_LINES),
+      num_width: self.num_width,
+      account_amount_spacing: self.account_amount_spacing,
+      long_currency: self.long_currency.unwrap_or(DEFAULT_LONG_CURRENCY),
+      number_currency_spacing: self
+        .number_currency_spacing
+        .unwrap_or(DEFAULT_NUMBER_CURRENCY_SPACING),
+      preserve_posting_blank_lines: self
+        .preserve_posting_blank_lines
+        .unwrap_or(DEFAULT_PRESERVE_POSTING_BLANK_LINES),
+      currency_position: self.currency_position.unwrap_or(DEFAULT_CURRENCY_POSITION),
+      normalize_include_paths: self
+        .normalize_include_paths
+        .unwrap_or(DEFAULT_NORMALIZE_INCLUDE_PATHS),
+      prefix_width: self.prefix_width,
+      verify_semantics: self.verify_semantics.unwrap_or(DEFAULT_VERIFY_SEMANTICS),
+      use_tabs: self.use_tabs.unwrap_or(DEFAULT_USE_TABS),
+      global_account_alignment: self
+        .global_account_alignment
+        .unwrap_or(DEFAULT_GLOBAL_ACCOUNT_ALIGNMENT),
+      respect_column_hints: self
+        .respect_column_hints
+        .unwrap_or(DEFAULT_RESPECT_COLUMN_HINTS),
+      metadata_extra_indent: self
+        .metadata_extra_indent
+        .unwrap_or(DEFAULT_METADATA_EXTRA_INDENT),
+      blank_lines: self.blank_lines.unwrap_or(DEFAULT_BLANK_LINES),
+      alignment_tolerance: self
+        .alignment_tolerance
+        .unwrap_or(DEFAULT_ALIGNMENT_TOLERANCE),
+      sort_tags_links: self.sort_tags_links.unwrap_or(DEFAULT_SORT_TAGS_LINKS),
+      tag_link_grouping: self.tag_link_grouping.unwrap_or(DEFAULT_TAG_LINK_GROUPING),
+      date_separator: self.date_separator.unwrap_or(DEFAULT_DATE_SEPARATOR),
+      sort_directives: self.sort_directives.unwrap_or(DEFAULT_SORT_DIRECTIVES),
+      sort_postings: self.sort_postings.unwrap_or(DEFAULT_SORT_POSTINGS),
+      max_blank_lines: self.max_blank_lines.unwrap_or(DEFAULT_MAX_BLANK_LINES),
+      blank_lines_around_transactions: self
+        .blank_lines_around_transactions
+        .unwrap_or(DEFAULT_BLANK_LINES_AROUND_TRANSACTIONS),
+      align_custom: self.align_custom.unwrap_or(DEFAULT_ALIGN_CUSTOM),
+      comment_column: self.comment_column,
+      comment_anchoring: self.comment_anchoring.unwrap_or(DEFAULT_COMMENT_ANCHORING),
+      flag_in_margin: self.flag_in_margin.unwrap_or(DEFAULT_FLAG_IN_MARGIN),
+      long_comment_placement: self
+        .long_comment_placement
+        .unwrap_or(DEFAULT_LONG_COMMENT_PLACEMENT),
+      normalize_metadata_dates: self
+        .normalize_metadata_dates
+        .unwrap_or(DEFAULT_NORMALIZE_METADATA_DATES),
+      price_operator_spacing: self
+        .price_operator_spacing
+        .unwrap_or(DEFAULT_PRICE_OPERATOR_SPACING),
+      minimize_diff: self.minimize_diff.unwrap_or(DEFAULT_MINIMIZE_DIFF),
+      normalize_currency_case: self
+        .normalize_currency_case
+        .unwrap_or(DEFAULT_NORMALIZE_CURRENCY_CASE),
+      thousands_separator: self
+        .thousands_separator
+        .unwrap_or(DEFAULT_THOUSANDS_SEPARATOR),
+      trailing_newline: self.trailing_newline.unwrap_or(DEFAULT_TRAILING_NEWLINE),
     }
   }
 }
@@ -40,8 +352,107 @@ impl Default for Configuration {
     Self {
       line_width: DEFAULT_LINE_WIDTH,
       indent_width: DEFAULT_INDENT_WIDTH,
-      new_line: DEFAULT_NEW_LINE_KIND,
+      new_line_kind: DEFAULT_NEW_LINE_KIND,
       compact_balance_spacing: DEFAULT_COMPACT_BALANCE_SPACING,
+      minimal: DEFAULT_MINIMAL,
+      normalize_comment_metadata: DEFAULT_NORMALIZE_COMMENT_METADATA,
+      currency_column: None,
+      collapse_string_spaces: DEFAULT_COLLAPSE_STRING_SPACES,
+      align_keyword: DEFAULT_ALIGN_KEYWORD,
+      sort_by_date: DEFAULT_SORT_BY_DATE,
+      same_date_order: DEFAULT_SAME_DATE_ORDER,
+      trim_comment_trailing: DEFAULT_TRIM_COMMENT_TRAILING,
+      align_text_directives: DEFAULT_ALIGN_TEXT_DIRECTIVES,
+      preserve_leading_blank_lines: DEFAULT_PRESERVE_LEADING_BLANK_LINES,
+      num_width: None,
+      account_amount_spacing: None,
+      long_currency: DEFAULT_LONG_CURRENCY,
+      number_currency_spacing: DEFAULT_NUMBER_CURRENCY_SPACING,
+      preserve_posting_blank_lines: DEFAULT_PRESERVE_POSTING_BLANK_LINES,
+      currency_position: DEFAULT_CURRENCY_POSITION,
+      normalize_include_paths: DEFAULT_NORMALIZE_INCLUDE_PATHS,
+      prefix_width: None,
+      verify_semantics: DEFAULT_VERIFY_SEMANTICS,
+      use_tabs: DEFAULT_USE_TABS,
+      global_account_alignment: DEFAULT_GLOBAL_ACCOUNT_ALIGNMENT,
+      respect_column_hints: DEFAULT_RESPECT_COLUMN_HINTS,
+      metadata_extra_indent: DEFAULT_METADATA_EXTRA_INDENT,
+      blank_lines: DEFAULT_BLANK_LINES,
+      alignment_tolerance: DEFAULT_ALIGNMENT_TOLERANCE,
+      sort_tags_links: DEFAULT_SORT_TAGS_LINKS,
+      tag_link_grouping: DEFAULT_TAG_LINK_GROUPING,
+      date_separator: DEFAULT_DATE_SEPARATOR,
+      sort_directives: DEFAULT_SORT_DIRECTIVES,
+      sort_postings: DEFAULT_SORT_POSTINGS,
+      max_blank_lines: DEFAULT_MAX_BLANK_LINES,
+      blank_lines_around_transactions: DEFAULT_BLANK_LINES_AROUND_TRANSACTIONS,
+      align_custom: DEFAULT_ALIGN_CUSTOM,
+      comment_column: None,
+      comment_anchoring: DEFAULT_COMMENT_ANCHORING,
+      flag_in_margin: DEFAULT_FLAG_IN_MARGIN,
+      long_comment_placement: DEFAULT_LONG_COMMENT_PLACEMENT,
+      normalize_metadata_dates: DEFAULT_NORMALIZE_METADATA_DATES,
+      price_operator_spacing: DEFAULT_PRICE_OPERATOR_SPACING,
+      minimize_diff: DEFAULT_MINIMIZE_DIFF,
+      normalize_currency_case: DEFAULT_NORMALIZE_CURRENCY_CASE,
+      thousands_separator: DEFAULT_THOUSANDS_SEPARATOR,
+      trailing_newline: DEFAULT_TRAILING_NEWLINE,
     }
   }
 }
+
+/// A setting that contradicts another one, reported by
+/// [`Configuration::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigurationDiagnostic {
+  /// The setting that is ignored or can't be honored.
+  pub property_name: String,
+  pub message: String,
+}
+
+impl Display for ConfigurationDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.property_name, self.message)
+  }
+}
+
+impl Configuration {
+  /// Checks for settings that contradict each other, e.g. for a configuration
+  /// built as a struct literal instead of resolved from a
+  /// [`PartialConfiguration`]. An empty list means the configuration is
+  /// consistent.
+  pub fn validate(&self) -> Vec<ConfigurationDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |property_name: &str, message: String| {
+      diagnostics.push(ConfigurationDiagnostic {
+        property_name: property_name.to_string(),
+        message,
+      });
+    };
+
+    if self.currency_column.is_some() && self.account_amount_spacing.is_some() {
+      report(
+        "currency_column",
+        "is ignored because account_amount_spacing is set".to_string(),
+      );
+    }
+    if let Some(num_width) = self.num_width
+      && num_width > self.line_width
+    {
+      report(
+        "num_width",
+        format!("{num_width} is larger than line_width {}", self.line_width),
+      );
+    }
+    if self.use_tabs && self.indent_width == 0 {
+      report(
+        "indent_width",
+        "must be at least 1 with use_tabs, which replaces each indent_width \
+         spaces with a tab"
+          .to_string(),
+      );
+    }
+
+    diagnostics
+  }
+}
@@ -0,0 +1,49 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Where an amount's currency is written relative to its number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurrencyPosition {
+  /// `100.00 USD`, the only order beancount accepts.
+  #[serde(rename = "after")]
+  After,
+  /// `USD 100.00`, for display or report exports only. The output is not valid
+  /// beancount and won't parse again.
+  #[serde(rename = "before")]
+  Before,
+}
+
+impl CurrencyPosition {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CurrencyPosition::After => "after",
+      CurrencyPosition::Before => "before",
+    }
+  }
+
+  /// Parse a currency position from a string. Accepts case-insensitive "after"
+  /// or "before".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "after" => Ok(CurrencyPosition::After),
+      "before" => Ok(CurrencyPosition::Before),
+      other => Err(format!("Unsupported currency_position: {}", other)),
+    }
+  }
+}
+
+impl Display for CurrencyPosition {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for CurrencyPosition {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
@@ -0,0 +1,55 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Separator written between the year, month and day of directive dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateSeparator {
+  /// `2024-01-31`
+  #[serde(rename = "-", alias = "dash")]
+  Dash,
+  /// `2024/01/31`
+  #[serde(rename = "/", alias = "slash")]
+  Slash,
+}
+
+impl DateSeparator {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DateSeparator::Dash => "-",
+      DateSeparator::Slash => "/",
+    }
+  }
+
+  pub fn as_char(&self) -> char {
+    match self {
+      DateSeparator::Dash => '-',
+      DateSeparator::Slash => '/',
+    }
+  }
+
+  /// Parse a separator from a string. Accepts "-" or "/", or the
+  /// case-insensitive names "dash" and "slash".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "-" | "dash" => Ok(DateSeparator::Dash),
+      "/" | "slash" => Ok(DateSeparator::Slash),
+      other => Err(format!("Unsupported date_separator: {}", other)),
+    }
+  }
+}
+
+impl Display for DateSeparator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for DateSeparator {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
@@ -0,0 +1,54 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Where an inline comment goes when it doesn't fit within `line_width`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LongCommentPlacement {
+  /// Keep the comment at the end of the line, past `line_width`.
+  #[serde(rename = "trailing")]
+  Trailing,
+  /// Move the comment to its own line above the line it was on.
+  #[serde(rename = "above")]
+  Above,
+  /// Move the comment to its own line below the line it was on, indented
+  /// like metadata when that line is not indented.
+  #[serde(rename = "below")]
+  Below,
+}
+
+impl LongCommentPlacement {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LongCommentPlacement::Trailing => "trailing",
+      LongCommentPlacement::Above => "above",
+      LongCommentPlacement::Below => "below",
+    }
+  }
+
+  /// Parse a placement from a string. Accepts case-insensitive "trailing",
+  /// "above" or "below".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "trailing" => Ok(LongCommentPlacement::Trailing),
+      "above" => Ok(LongCommentPlacement::Above),
+      "below" => Ok(LongCommentPlacement::Below),
+      other => Err(format!("Unsupported long_comment_placement: {}", other)),
+    }
+  }
+}
+
+impl Display for LongCommentPlacement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for LongCommentPlacement {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
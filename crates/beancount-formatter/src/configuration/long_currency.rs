@@ -0,0 +1,49 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// What `currency_column` alignment does with an amount that would run past
+/// the line width, e.g. because of a long ticker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LongCurrency {
+  /// Keep the currency column and let the line overflow.
+  #[serde(rename = "overflow")]
+  Overflow,
+  /// Move the amount left, down to a single space, to stay within the width.
+  #[serde(rename = "shrink")]
+  Shrink,
+}
+
+impl LongCurrency {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LongCurrency::Overflow => "overflow",
+      LongCurrency::Shrink => "shrink",
+    }
+  }
+
+  /// Parse a long currency mode from a string. Accepts case-insensitive
+  /// "overflow" or "shrink".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "overflow" => Ok(LongCurrency::Overflow),
+      "shrink" => Ok(LongCurrency::Shrink),
+      other => Err(format!("Unsupported long_currency: {}", other)),
+    }
+  }
+}
+
+impl Display for LongCurrency {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for LongCurrency {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
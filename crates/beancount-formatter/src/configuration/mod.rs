@@ -1,6 +1,24 @@
+mod blank_lines;
+mod comment_anchoring;
 #[allow(clippy::module_inception)]
 mod configuration;
+mod currency_position;
+mod date_separator;
+mod long_comment_placement;
+mod long_currency;
 mod new_line_kind;
+mod same_date_order;
+mod tag_link_grouping;
+mod thousands_separator;
 
+pub use blank_lines::*;
+pub use comment_anchoring::*;
 pub use configuration::*;
+pub use currency_position::*;
+pub use date_separator::*;
+pub use long_comment_placement::*;
+pub use long_currency::*;
 pub use new_line_kind::*;
+pub use same_date_order::*;
+pub use tag_link_grouping::*;
+pub use thousands_separator::*;
@@ -0,0 +1,49 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Tie-break used when sorting directives that share the same date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SameDateOrder {
+  /// Keep the order the directives appear in the source.
+  #[serde(rename = "source")]
+  Source,
+  /// Order by directive type the way beancount does: `open`, then `balance`,
+  /// then everything else, then `document`, then `close`.
+  #[serde(rename = "type_priority", alias = "type-priority")]
+  TypePriority,
+}
+
+impl SameDateOrder {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SameDateOrder::Source => "source",
+      SameDateOrder::TypePriority => "type_priority",
+    }
+  }
+
+  /// Parse a tie-break mode from a string. Accepts case-insensitive "source" or
+  /// "type_priority" (also spelled "type-priority").
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "source" => Ok(SameDateOrder::Source),
+      "type_priority" | "type-priority" => Ok(SameDateOrder::TypePriority),
+      other => Err(format!("Unsupported same_date_order: {}", other)),
+    }
+  }
+}
+
+impl Display for SameDateOrder {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for SameDateOrder {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
@@ -0,0 +1,53 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Whether `#tags` and `^links` after a header are grouped by kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagLinkGrouping {
+  /// Keep tags and links in the order they are written.
+  #[serde(rename = "source")]
+  Source,
+  /// All tags, then all links.
+  #[serde(rename = "tags_first", alias = "tags-first")]
+  TagsFirst,
+  /// All links, then all tags.
+  #[serde(rename = "links_first", alias = "links-first")]
+  LinksFirst,
+}
+
+impl TagLinkGrouping {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TagLinkGrouping::Source => "source",
+      TagLinkGrouping::TagsFirst => "tags_first",
+      TagLinkGrouping::LinksFirst => "links_first",
+    }
+  }
+
+  /// Parse a grouping from a string. Accepts case-insensitive "source",
+  /// "tags_first" or "links_first" (also spelled with `-`).
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "source" => Ok(TagLinkGrouping::Source),
+      "tags_first" | "tags-first" => Ok(TagLinkGrouping::TagsFirst),
+      "links_first" | "links-first" => Ok(TagLinkGrouping::LinksFirst),
+      other => Err(format!("Unsupported tag_link_grouping: {}", other)),
+    }
+  }
+}
+
+impl Display for TagLinkGrouping {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for TagLinkGrouping {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
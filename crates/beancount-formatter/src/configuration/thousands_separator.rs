@@ -0,0 +1,53 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// How `,` thousands separators in the integer part of numbers are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThousandsSeparator {
+  /// Keep numbers as written.
+  #[serde(rename = "preserve")]
+  Preserve,
+  /// Remove every separator: `1,234.56` becomes `1234.56`.
+  #[serde(rename = "none", alias = "strip")]
+  Strip,
+  /// Group every three integer digits: `1234.56` becomes `1,234.56`.
+  #[serde(rename = "comma")]
+  Comma,
+}
+
+impl ThousandsSeparator {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ThousandsSeparator::Preserve => "preserve",
+      ThousandsSeparator::Strip => "none",
+      ThousandsSeparator::Comma => "comma",
+    }
+  }
+
+  /// Parse a separator style from a string. Accepts case-insensitive
+  /// "preserve", "none" (or "strip") and "comma".
+  pub fn parse(text: &str) -> Result<Self, String> {
+    match text.to_ascii_lowercase().as_str().trim() {
+      "preserve" => Ok(ThousandsSeparator::Preserve),
+      "none" | "strip" => Ok(ThousandsSeparator::Strip),
+      "comma" => Ok(ThousandsSeparator::Comma),
+      other => Err(format!("Unsupported thousands_separator: {}", other)),
+    }
+  }
+}
+
+impl Display for ThousandsSeparator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for ThousandsSeparator {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
@@ -0,0 +1,125 @@
+use std::fmt::{self, Display};
+
+use crate::parse::date_parts;
+
+/// A posting cost such as `{100.00 USD, 2020-01-01, "lot-1"}`, split into its
+/// components so they can be written in one canonical order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CostSpec {
+  /// `{{...}}`: the amount is the total cost instead of the per-unit cost.
+  pub total: bool,
+  /// Per-unit or total amount, possibly compound (`10 # 5 USD`).
+  pub amount: Option<String>,
+  /// Lot date, `YYYY-MM-DD`.
+  pub date: Option<String>,
+  /// Lot label, including its quotes.
+  pub label: Option<String>,
+  /// `*`: merge all lots of the position at their average cost.
+  pub merge: bool,
+}
+
+impl CostSpec {
+  /// Parses the cost text as written, braces included. Returns `None` for
+  /// anything that doesn't look like a cost, e.g. a component given twice.
+  pub fn parse(raw: &str) -> Option<Self> {
+    let raw = raw.trim();
+    let (total, inner) = match raw.strip_prefix("{{") {
+      Some(rest) => (true, rest.strip_suffix("}}")?),
+      None => (false, raw.strip_prefix('{')?.strip_suffix('}')?),
+    };
+
+    let mut spec = CostSpec {
+      total,
+      amount: None,
+      date: None,
+      label: None,
+      merge: false,
+    };
+    for component in split_components(inner) {
+      let component = component.split_whitespace().collect::<Vec<_>>().join(" ");
+      if component.is_empty() {
+        continue;
+      }
+      if component == "*" {
+        if spec.merge {
+          return None;
+        }
+        spec.merge = true;
+        continue;
+      }
+      let slot = if component.starts_with('"') {
+        &mut spec.label
+      } else if date_parts(&component).is_some() {
+        &mut spec.date
+      } else {
+        &mut spec.amount
+      };
+      if slot.replace(component).is_some() {
+        return None;
+      }
+    }
+    Some(spec)
+  }
+}
+
+impl Display for CostSpec {
+  /// Writes amount, date, label and merge flag in that order, separated by `, `.
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut components: Vec<&str> = [&self.amount, &self.date, &self.label]
+      .into_iter()
+      .flatten()
+      .map(String::as_str)
+      .collect();
+    if self.merge {
+      components.push("*");
+    }
+    let (open, close) = if self.total { ("{{", "}}") } else { ("{", "}") };
+    write!(f, "{}{}{}", open, components.join(", "), close)
+  }
+}
+
+/// Splits on commas outside of string literals, except thousands separators
+/// inside numbers like `1,000.00`.
+fn split_components(text: &str) -> Vec<&str> {
+  let mut components = Vec::new();
+  let mut start = 0;
+  let mut in_string = false;
+  let mut escape = false;
+  for (idx, ch) in text.char_indices() {
+    if in_string {
+      if escape {
+        escape = false;
+      } else if ch == '\\' {
+        escape = true;
+      } else if ch == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    match ch {
+      '"' => in_string = true,
+      ',' if !is_thousands_separator(text, idx) => {
+        components.push(&text[start..idx]);
+        start = idx + 1;
+      }
+      _ => {}
+    }
+  }
+  components.push(&text[start..]);
+  components
+}
+
+/// Whether the `,` at byte `idx` of `text` groups the digits of a number: it
+/// follows a digit and exactly three digits follow it. A date after a comma,
+/// as in `{100,2020-01-01}`, isn't a digit group.
+pub(crate) fn is_thousands_separator(text: &str, idx: usize) -> bool {
+  let bytes = text.as_bytes();
+  let after = &bytes[idx + 1..];
+  idx > 0
+    && bytes[idx - 1].is_ascii_digit()
+    && after.len() >= 3
+    && after[..3].iter().all(u8::is_ascii_digit)
+    && after
+      .get(3)
+      .is_none_or(|b| !b.is_ascii_digit() && *b != b'-' && *b != b'/')
+}
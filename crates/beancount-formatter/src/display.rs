@@ -0,0 +1,58 @@
+use anyhow::Result;
+
+use crate::configuration::Configuration;
+use crate::format::format;
+use crate::parse::{directive_accounts, parse_source, with_trailing_newline};
+
+/// Formats `source` for showing to a person, e.g. in a narrow terminal. With
+/// `abbreviate`, every account is shortened to the initials of its parent
+/// components, so `Assets:Investments:Brokerage:Vanguard` becomes
+/// `A:I:B:Vanguard`, and columns are aligned for the shorter names.
+///
+/// **The output is lossy.** The full account names can't be recovered from
+/// their abbreviations, so never write it back over the source file.
+pub fn format_for_display(
+  source: &str,
+  config: &Configuration,
+  abbreviate: bool,
+) -> Result<String> {
+  if !abbreviate {
+    return format(source, config);
+  }
+  let content = with_trailing_newline(source);
+
+  let mut abbreviated = String::with_capacity(content.len());
+  let mut copied = 0;
+  for dir in parse_source(&content) {
+    for account in directive_accounts(&dir) {
+      let name = account.content.trim();
+      let start = account.span.start + account.content.find(name).unwrap_or(0);
+      if start < copied {
+        continue;
+      }
+      abbreviated.push_str(&content[copied..start]);
+      abbreviated.push_str(&abbreviate_account(name));
+      copied = start + name.len();
+    }
+  }
+  abbreviated.push_str(&content[copied..]);
+  format(&abbreviated, config)
+}
+
+/// `account` with every component but the last cut to its first character.
+fn abbreviate_account(account: &str) -> String {
+  let mut components: Vec<&str> = account.split(':').collect();
+  let last = components.pop().unwrap_or_default();
+  components
+    .into_iter()
+    .map(|component| {
+      component
+        .chars()
+        .next()
+        .map(String::from)
+        .unwrap_or_default()
+    })
+    .chain([last.to_string()])
+    .collect::<Vec<_>>()
+    .join(":")
+}
@@ -1,7 +1,21 @@
-use anyhow::Result;
-
-use crate::configuration::{Configuration, NewLineKind};
-use crate::parse::parse_source;
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use unicode_width::UnicodeWidthStr;
+
+use crate::configuration::{
+  BlankLines, CommentAnchoring, Configuration, CurrencyPosition, LongCommentPlacement,
+  LongCurrency, NewLineKind, SameDateOrder, TagLinkGrouping, ThousandsSeparator,
+};
+use crate::cost::{CostSpec, is_thousands_separator};
+use crate::lint::is_metadata_key;
+use crate::parse::{
+  SyntaxError, date_parts, parse_source, syntax_errors, with_trailing_newline,
+};
+use crate::raw::RawFormatters;
+use crate::verify::verify_semantics;
 use beancount_parser::ast::{self, Directive, PriceOperator, WithSpan};
 
 /// Simple string writer to avoid building large intermediate vectors before concatenation.
@@ -12,53 +26,57 @@ struct Writer {
 fn format_open(writer: &mut Writer, d: &ast::Open<'_>, config: &Configuration) {
   let comment_col = config.line_width as usize;
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("open".to_string()),
     Some(to_part(&d.account)),
   ]);
-  line = align_trailing(line, format_currencies(&d.currencies), comment_col);
+  // The booking method follows the currencies and is aligned along with them.
+  let booking = d.opt_booking.as_ref().map(to_part);
+  let trailing = match (format_currencies(&d.currencies, config), booking) {
+    (Some(currencies), Some(booking)) => Some(format!("{currencies} {booking}")),
+    (currencies, booking) => currencies.or(booking),
+  };
+  line = align_trailing(line, trailing, comment_col);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, true);
+    line = append_comment(line, &format_comment(comment, config), config, true);
   }
   writer.write_str(&line);
 }
 
 fn format_close(writer: &mut Writer, d: &ast::Close<'_>, config: &Configuration) {
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("close".to_string()),
     Some(to_part(&d.account)),
   ]);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
 
 fn format_balance(writer: &mut Writer, d: &ast::Balance<'_>, config: &Configuration) {
-  let comment_col = config.line_width as usize;
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("balance".to_string()),
     Some(to_part(&d.account)),
   ]);
-  let trailing = format_amount(&d.amount);
-  line = align_trailing(line, trailing, comment_col);
+  line = place_amount(line, &d.amount, config);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, true);
+    line = append_comment(line, &format_comment(comment, config), config, true);
   }
   writer.write_str(&line);
 }
 
 fn format_pad(writer: &mut Writer, d: &ast::Pad<'_>, config: &Configuration) {
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("pad".to_string()),
     Some(to_part(&d.account)),
     Some(to_part(&d.from_account)),
   ]);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
@@ -67,107 +85,127 @@ fn format_commodity(
   writer: &mut Writer,
   d: &ast::Commodity<'_>,
   config: &Configuration,
+  operand_column: Option<usize>,
 ) {
   let comment_col = config.line_width as usize;
-  let mut line = join_parts([Some(to_part(&d.date)), Some("commodity".to_string())]);
-  line = align_trailing(line, Some(to_part(&d.currency)), comment_col);
+  let mut line = commodity_prefix(d, config);
+  let currency = format_currency(d.currency.content, config);
+  line = match operand_column {
+    Some(column) => align_at_column(line, &currency, 0, column),
+    None => align_trailing(line, Some(currency), comment_col),
+  };
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, true);
+    line = append_comment(line, &format_comment(comment, config), config, true);
   }
   writer.write_str(&line);
 }
 
 fn format_price(writer: &mut Writer, d: &ast::Price<'_>, config: &Configuration) {
-  let comment_col = config.line_width as usize;
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("price".to_string()),
-    Some(to_part(&d.currency)),
+    Some(format_currency(d.currency.content, config)),
   ]);
-  let trailing = format_amount(&d.amount);
-  line = align_trailing(line, trailing, comment_col);
+  line = place_amount(line, &d.amount, config);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, true);
+    line = append_comment(line, &format_comment(comment, config), config, true);
   }
   writer.write_str(&line);
 }
 
-fn format_event(writer: &mut Writer, d: &ast::Event<'_>, config: &Configuration) {
-  let mut line = join_parts([
-    Some(to_part(&d.date)),
-    Some("event".to_string()),
-    Some(to_part(&d.event_type)),
-    Some(to_part(&d.desc)),
-  ]);
+fn format_event(
+  writer: &mut Writer,
+  d: &ast::Event<'_>,
+  config: &Configuration,
+  text_column: Option<usize>,
+) {
+  let mut line = place_operand(event_prefix(d, config), to_part(&d.desc), text_column);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
 
+fn event_prefix(d: &ast::Event<'_>, config: &Configuration) -> String {
+  join_parts([
+    Some(format_date(&d.date, config)),
+    Some("event".to_string()),
+    Some(to_part(&d.event_type)),
+  ])
+}
+
 fn format_query(writer: &mut Writer, d: &ast::Query<'_>, config: &Configuration) {
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("query".to_string()),
     Some(to_part(&d.name)),
     Some(to_part(&d.query)),
   ]);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
 
 fn format_note(writer: &mut Writer, d: &ast::Note<'_>, config: &Configuration) {
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("note".to_string()),
     Some(to_part(&d.account)),
     Some(to_part(&d.note)),
   ]);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
 
 fn format_document(writer: &mut Writer, d: &ast::Document<'_>, config: &Configuration) {
   let mut line = join_parts([
-    Some(to_part(&d.date)),
+    Some(format_date(&d.date, config)),
     Some("document".to_string()),
     Some(to_part(&d.account)),
     Some(to_part(&d.filename)),
-    format_tags_links(&d.tags_links),
+    format_tags_links(d.tags_links.iter().flatten().map(|tag| tag.content), config),
   ]);
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
 
-fn format_custom(writer: &mut Writer, d: &ast::Custom<'_>, config: &Configuration) {
-  let mut line = join_parts([
-    Some(to_part(&d.date)),
-    Some("custom".to_string()),
-    Some(to_part(&d.name)),
-    if d.values.is_empty() {
-      None
-    } else {
-      Some(
-        d.values
-          .iter()
-          .map(|v| v.raw.content.trim())
-          .collect::<Vec<_>>()
-          .join(" "),
-      )
-    },
-  ]);
+fn format_custom(
+  writer: &mut Writer,
+  d: &ast::Custom<'_>,
+  config: &Configuration,
+  values_column: Option<usize>,
+) {
+  let prefix = custom_prefix(d, config);
+  let mut line = if d.values.is_empty() {
+    prefix
+  } else {
+    let values = d
+      .values
+      .iter()
+      .map(|v| normalize_value_date(v.raw.content.trim(), config))
+      .collect::<Vec<_>>()
+      .join(" ");
+    place_operand(prefix, values, values_column)
+  };
   if let Some(comment) = &d.comment {
-    line = append_comment(line, &format_comment(comment), config, false);
+    line = append_comment(line, &format_comment(comment, config), config, false);
   }
   writer.write_str(&line);
 }
 
+fn custom_prefix(d: &ast::Custom<'_>, config: &Configuration) -> String {
+  join_parts([
+    Some(format_date(&d.date, config)),
+    Some("custom".to_string()),
+    Some(to_part(&d.name)),
+  ])
+}
+
 fn format_option(writer: &mut Writer, d: &ast::OptionDirective<'_>) {
   let line = join_parts([
     Some("option".to_string()),
@@ -177,11 +215,45 @@ fn format_option(writer: &mut Writer, d: &ast::OptionDirective<'_>) {
   writer.write_str(&line);
 }
 
-fn format_include(writer: &mut Writer, d: &ast::Include<'_>) {
-  let line = join_parts([Some("include".to_string()), Some(to_part(&d.filename))]);
+fn format_include(writer: &mut Writer, d: &ast::Include<'_>, config: &Configuration) {
+  let mut filename = to_part(&d.filename);
+  if config.normalize_include_paths {
+    filename = normalize_include_path(&filename);
+  }
+  let line = join_parts([Some("include".to_string()), Some(filename)]);
   writer.write_str(&line);
 }
 
+/// The quoted `include` filename `literal` with its `\` path separators written
+/// as `/`. The string is unescaped first and quoted again afterwards, so an
+/// escaped quote (`\"`) stays part of the name.
+pub(crate) fn normalize_include_path(literal: &str) -> String {
+  let Some(inner) = literal
+    .strip_prefix('"')
+    .and_then(|rest| rest.strip_suffix('"'))
+  else {
+    return literal.to_string();
+  };
+  let mut path = String::with_capacity(inner.len());
+  let mut chars = inner.chars();
+  while let Some(ch) = chars.next() {
+    if ch != '\\' {
+      path.push(ch);
+      continue;
+    }
+    match chars.next() {
+      Some('"') => path.push('"'),
+      // An escaped backslash (`\\`) is one path separator, like a lone one.
+      Some('\\') | None => path.push('/'),
+      Some(next) => {
+        path.push('/');
+        path.push(next);
+      }
+    }
+  }
+  format!("\"{}\"", path.replace('"', "\\\""))
+}
+
 fn format_plugin(writer: &mut Writer, d: &ast::Plugin<'_>) {
   let line = join_parts([
     Some("plugin".to_string()),
@@ -191,16 +263,22 @@ fn format_plugin(writer: &mut Writer, d: &ast::Plugin<'_>) {
   writer.write_str(&line);
 }
 
-fn format_pushtag(writer: &mut Writer, d: &ast::TagDirective<'_>) {
+fn format_pushtag(
+  writer: &mut Writer,
+  d: &ast::TagDirective<'_>,
+  operand_column: Option<usize>,
+) {
   let tag = format!("#{}", to_part(&d.tag));
-  let line = join_parts([Some("pushtag".to_string()), Some(tag)]);
-  writer.write_str(&line);
+  writer.write_str(&place_operand("pushtag".to_string(), tag, operand_column));
 }
 
-fn format_poptag(writer: &mut Writer, d: &ast::TagDirective<'_>) {
+fn format_poptag(
+  writer: &mut Writer,
+  d: &ast::TagDirective<'_>,
+  operand_column: Option<usize>,
+) {
   let tag = format!("#{}", to_part(&d.tag));
-  let line = join_parts([Some("poptag".to_string()), Some(tag)]);
-  writer.write_str(&line);
+  writer.write_str(&place_operand("poptag".to_string(), tag, operand_column));
 }
 
 fn format_pushmeta(writer: &mut Writer, d: &ast::PushMeta<'_>) {
@@ -216,12 +294,76 @@ fn format_pushmeta(writer: &mut Writer, d: &ast::PushMeta<'_>) {
   writer.write_str(&line);
 }
 
-fn format_popmeta(writer: &mut Writer, d: &ast::PopMeta<'_>) {
-  let line = join_parts([
-    Some("popmeta".to_string()),
-    Some(format!("{}:", to_part(&d.key))),
-  ]);
-  writer.write_str(&line);
+fn format_popmeta(
+  writer: &mut Writer,
+  d: &ast::PopMeta<'_>,
+  operand_column: Option<usize>,
+) {
+  let key = format!("{}:", to_part(&d.key));
+  writer.write_str(&place_operand("popmeta".to_string(), key, operand_column));
+}
+
+fn commodity_prefix(d: &ast::Commodity<'_>, config: &Configuration) -> String {
+  join_parts([
+    Some(format_date(&d.date, config)),
+    Some("commodity".to_string()),
+  ])
+}
+
+/// Returns the keyword (and date) text preceding the operand of a
+/// single-operand directive, or `None` for any other directive.
+fn single_operand_prefix(
+  dir: &Directive<'_>,
+  config: &Configuration,
+) -> Option<String> {
+  match dir {
+    Directive::Commodity(d) => Some(commodity_prefix(d, config)),
+    Directive::PushTag(_) => Some("pushtag".to_string()),
+    Directive::PopTag(_) => Some("poptag".to_string()),
+    Directive::PopMeta(_) => Some("popmeta".to_string()),
+    _ => None,
+  }
+}
+
+/// Stably reorders the postings of a transaction by account. Each posting moves
+/// together with the lines below it up to the next posting, such as its
+/// metadata.
+fn sort_posting_blocks<T>(
+  lines: &mut Vec<T>,
+  txn: &ast::Transaction<'_>,
+  posting_lines: &[(usize, bool)],
+) {
+  let mut starts: Vec<(usize, &str)> = txn
+    .postings
+    .iter()
+    .zip(posting_lines)
+    .filter(|(_, (_, owns_line))| *owns_line)
+    .map(|(posting, (line_idx, _))| (*line_idx, posting.account.content.trim()))
+    .collect();
+  starts.sort_by_key(|(line_idx, _)| *line_idx);
+  let Some(&(first, _)) = starts.first() else {
+    return;
+  };
+  let mut tail = lines.split_off(first);
+  let mut blocks: Vec<(&str, Vec<T>)> = Vec::with_capacity(starts.len());
+  for (line_idx, account) in starts.into_iter().rev() {
+    blocks.push((account, tail.split_off(line_idx - first)));
+  }
+  blocks.reverse();
+  // `sort_by_key` is stable, so postings to the same account keep their order.
+  blocks.sort_by_key(|(account, _)| *account);
+  for (_, block) in blocks {
+    lines.extend(block);
+  }
+}
+
+/// Joins `prefix` and `operand`, starting the operand at the 1-based `column`
+/// when column alignment applies.
+fn place_operand(prefix: String, operand: String, column: Option<usize>) -> String {
+  match column {
+    Some(column) => align_at_column(prefix, &operand, 0, column),
+    None => join_parts([Some(prefix), Some(operand)]),
+  }
 }
 
 impl Writer {
@@ -240,16 +382,72 @@ impl Writer {
   }
 }
 
+/// The whitespace-separated tokens of `text`, with string literals kept whole
+/// so spacing inside them counts.
+fn layout_tokens(text: &str) -> Vec<&str> {
+  let mut tokens = Vec::new();
+  let mut start = None;
+  let mut in_string = false;
+  let mut escape = false;
+  for (idx, ch) in text.char_indices() {
+    if in_string {
+      if escape {
+        escape = false;
+      } else if ch == '\\' {
+        escape = true;
+      } else if ch == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    if ch.is_whitespace() {
+      if let Some(token_start) = start.take() {
+        tokens.push(&text[token_start..idx]);
+      }
+      continue;
+    }
+    start.get_or_insert(idx);
+    in_string = ch == '"';
+  }
+  if let Some(token_start) = start {
+    tokens.push(&text[token_start..]);
+  }
+  tokens
+}
+
+/// Column positions shared by a block of consecutive directives.
+#[derive(Clone, Copy, Default)]
+struct RunLayout {
+  /// 1-based column where the operand of a single-operand directive starts.
+  operand_column: Option<usize>,
+  /// 1-based column where the trailing string of a text directive starts.
+  text_column: Option<usize>,
+  /// 1-based column where the values of a `custom` directive start.
+  values_column: Option<usize>,
+}
+
 struct FormatterContext<'a> {
   config: &'a Configuration,
+  raw_formatters: &'a RawFormatters,
   writer: Writer,
+  layout: RunLayout,
+  /// Shared amount column of `global_account_alignment`, if any posting in the
+  /// file has an amount.
+  file_amount_column: Option<usize>,
 }
 
 impl<'a> FormatterContext<'a> {
-  fn new(config: &'a Configuration, capacity: usize) -> Self {
+  fn new(
+    config: &'a Configuration,
+    raw_formatters: &'a RawFormatters,
+    capacity: usize,
+  ) -> Self {
     Self {
       config,
+      raw_formatters,
       writer: Writer::with_capacity(capacity),
+      layout: RunLayout::default(),
+      file_amount_column: None,
     }
   }
 
@@ -263,14 +461,50 @@ impl<'a> FormatterContext<'a> {
 
   fn format_span(&mut self, span: ast::Span, full_source: &str) {
     let slice = &full_source[span.start..span.end];
-    self.write(&normalize_indentation(slice, self.config.indent_width));
+    self.write(&normalize_indentation(slice, self.config));
     // normalize_indentation already wrote trailing newlines; caller adds newline.
     if self.writer.buf.ends_with('\n') {
       self.writer.buf.pop();
     }
   }
 
+  /// Raw entries are kept as written, apart from line endings, unless a
+  /// formatter is registered for their kind.
+  fn format_raw(&mut self, span: ast::Span, full_source: &str) {
+    let text = full_source[span.start..span.end].replace("\r\n", "\n");
+    let text = text.trim_end_matches('\n');
+    match self.raw_formatters.format(text) {
+      Some(formatted) => self.write(formatted.trim_end_matches('\n')),
+      None => self.write(text),
+    }
+  }
+
   fn format_directive(&mut self, dir: &Directive<'a>, full_source: &str) {
+    let start = self.writer.buf.len();
+    self.write_directive(dir, full_source);
+    // Comments and raw entries are kept as written, so `use_tabs` only touches
+    // the lines generated for the other directives.
+    let verbatim = matches!(
+      dir,
+      Directive::Comment(_) | Directive::Headline(_) | Directive::Raw(_)
+    );
+    if self.config.use_tabs && !verbatim {
+      let tabified =
+        tabify_indentation(&self.writer.buf[start..], self.config.indent_width);
+      self.writer.buf.truncate(start);
+      self.writer.buf.push_str(&tabified);
+    }
+  }
+
+  fn write_directive(&mut self, dir: &Directive<'a>, full_source: &str) {
+    if self.config.minimal {
+      // Whitespace-only mode: keep the directive exactly as written apart from
+      // tab expansion and trailing whitespace.
+      self.format_span(directive_span(dir), full_source);
+      return;
+    }
+
+    let start = self.writer.buf.len();
     match dir {
       Directive::Open(d) => {
         format_open(&mut self.writer, d, self.config);
@@ -290,7 +524,8 @@ impl<'a> FormatterContext<'a> {
       }
       Directive::Transaction(d) => self.format_transaction(d, full_source),
       Directive::Commodity(d) => {
-        format_commodity(&mut self.writer, d, self.config);
+        let column = self.layout.operand_column;
+        format_commodity(&mut self.writer, d, self.config, column);
         self.format_key_values(&d.key_values, full_source);
       }
       Directive::Price(d) => {
@@ -298,7 +533,7 @@ impl<'a> FormatterContext<'a> {
         self.format_key_values(&d.key_values, full_source);
       }
       Directive::Event(d) => {
-        format_event(&mut self.writer, d, self.config);
+        format_event(&mut self.writer, d, self.config, self.layout.text_column);
         self.format_key_values(&d.key_values, full_source);
       }
       Directive::Query(d) => {
@@ -314,19 +549,36 @@ impl<'a> FormatterContext<'a> {
         self.format_key_values(&d.key_values, full_source);
       }
       Directive::Custom(d) => {
-        format_custom(&mut self.writer, d, self.config);
+        format_custom(&mut self.writer, d, self.config, self.layout.values_column);
         self.format_key_values(&d.key_values, full_source);
       }
       Directive::Option(d) => format_option(&mut self.writer, d),
-      Directive::Include(d) => format_include(&mut self.writer, d),
+      Directive::Include(d) => format_include(&mut self.writer, d, self.config),
       Directive::Plugin(d) => format_plugin(&mut self.writer, d),
-      Directive::PushTag(d) => format_pushtag(&mut self.writer, d),
-      Directive::PopTag(d) => format_poptag(&mut self.writer, d),
+      Directive::PushTag(d) => {
+        format_pushtag(&mut self.writer, d, self.layout.operand_column)
+      }
+      Directive::PopTag(d) => {
+        format_poptag(&mut self.writer, d, self.layout.operand_column)
+      }
       Directive::PushMeta(d) => format_pushmeta(&mut self.writer, d),
-      Directive::PopMeta(d) => format_popmeta(&mut self.writer, d),
+      Directive::PopMeta(d) => {
+        format_popmeta(&mut self.writer, d, self.layout.operand_column)
+      }
       Directive::Headline(d) => self.format_span(d.span, full_source),
       Directive::Comment(d) => self.format_span(d.span, full_source),
-      Directive::Raw(d) => self.format_span(d.span, full_source),
+      Directive::Raw(d) => self.format_raw(d.span, full_source),
+    }
+
+    // `minimize_diff` keeps the source when only the spacing between tokens
+    // changed.
+    if self.config.minimize_diff && !matches!(dir, Directive::Raw(_)) {
+      let span = directive_span(dir);
+      let source = &full_source[span.start..span.end];
+      if layout_tokens(&self.writer.buf[start..]) == layout_tokens(source) {
+        self.writer.buf.truncate(start);
+        self.format_span(span, full_source);
+      }
     }
   }
 
@@ -339,163 +591,575 @@ impl<'a> FormatterContext<'a> {
       .collect();
 
     let mut header_parts: Vec<String> = Vec::new();
-    header_parts.push(txn.date.content.trim().to_string());
+    header_parts.push(format_date(&txn.date, self.config));
     if let Some(flag) = &txn.txn {
       header_parts.push(flag.content.trim().to_string());
     }
     if let Some(payee) = &txn.payee {
-      header_parts.push(payee.content.trim().to_string());
+      header_parts.push(self.format_header_string(payee));
     }
     if let Some(narration) = &txn.narration {
-      header_parts.push(narration.content.trim().to_string());
+      header_parts.push(self.format_header_string(narration));
     }
-    if let Some(tags) = format_tags_links(&txn.tags_links) {
+    // Tags and links on their own lines below the header join the ones on it,
+    // so they all end up in one place.
+    let continued = tag_link_lines(&lines);
+    let continued_tags = lines
+      .iter()
+      .zip(&continued)
+      .filter(|(_, is_tag_line)| **is_tag_line)
+      .flat_map(|(line, _)| line.split_whitespace());
+    let tags = txn.tags_links.iter().flatten().map(|tag| tag.content);
+    if let Some(tags) = format_tags_links(tags.chain(continued_tags), self.config) {
       header_parts.push(tags);
     }
-    let mut header_line = header_parts.join(" ");
-    if let Some(comment) = &txn.comment {
-      header_line =
-        append_comment(header_line, &format_comment(comment), self.config, false);
-    }
-    lines[0] = header_line;
-
-    let mut posting_line_indices = Vec::new();
-    let mut min_indent = usize::MAX;
-
-    for posting in &txn.postings {
-      let offset = posting.span.start.saturating_sub(txn.span.start);
-      let line_idx = count_newlines_up_to(txn_text, offset);
-      posting_line_indices.push(line_idx);
-      if let Some(line) = lines.get(line_idx) {
-        let indent = leading_indent_width(line, self.config.indent_width);
-        min_indent = min_indent.min(indent);
+    // Skip empty parts so a missing field never leaves a double space behind.
+    header_parts.retain(|part| !part.is_empty());
+    // Comments moved off a long line by `long_comment_placement` get lines of
+    // their own around the line's slot.
+    let mut above: Vec<Vec<String>> = vec![Vec::new(); lines.len()];
+    let mut moved: Vec<Vec<String>> = vec![Vec::new(); lines.len()];
+
+    let header_line = header_parts.join(" ");
+    let header = match &txn.comment {
+      Some(comment) => {
+        let comment = format_comment(comment, self.config);
+        place_comment(header_line, &comment, self.config, false)
       }
-    }
-
-    if min_indent == usize::MAX {
-      min_indent = (self.config.indent_width as usize) * 2;
-    }
-
-    for (posting, &line_idx) in txn.postings.iter().zip(posting_line_indices.iter()) {
+      None => CommentedLine {
+        above: None,
+        line: header_line,
+        below: None,
+      },
+    };
+    above[0].extend(header.above);
+    lines[0] = header.line;
+    moved[0].extend(header.below);
+
+    let (posting_lines, min_indent) = posting_lines(txn, txn_text, self.config);
+
+    // `account_amount_spacing` puts every amount a fixed gap after its own
+    // account and disables alignment. Otherwise amounts start at a shared
+    // 1-based column: right after a fixed `prefix_width` or at the file-wide
+    // column of `global_account_alignment`, with `currency_column` taking
+    // precedence over both.
+    let fixed_gap = self
+      .config
+      .account_amount_spacing
+      .map(|gap| gap.max(1) as usize);
+    let amount_column = match (self.config.currency_column, self.config.prefix_width) {
+      (Some(_), _) => None,
+      (None, Some(prefix_width)) => Some(prefix_width as usize + 1),
+      (None, None) if self.config.global_account_alignment => self.file_amount_column,
+      (None, None) => None,
+    };
+
+    for (posting, &(line_idx, owns_line)) in txn.postings.iter().zip(&posting_lines) {
       let flag = posting.opt_flag.as_ref().map(|f| f.content.trim());
       let account = posting.account.content.trim();
-      let trailing =
-        if let Some(amount) = posting.amount.as_ref().and_then(format_amount) {
-          let mut parts = vec![amount];
-          if let Some(cost) = posting.cost_spec.as_ref() {
-            parts.push(compact_ws(cost.raw.content));
-          }
-          if let Some(price_op) = posting.price_operator.as_ref() {
-            parts.push(match price_op.content {
-              PriceOperator::PerUnit => "@".to_string(),
-              PriceOperator::Total => "@@".to_string(),
-            });
-          }
-          if let Some(price_ann) = posting.price_annotation.as_ref() {
-            parts.push(compact_ws(price_ann.raw.content));
+      let amount = posting
+        .amount
+        .as_ref()
+        .map(|amount| format_amount_parts(amount, self.config));
+      let trailing = if let Some((number, currency)) = amount.as_ref() {
+        let mut parts = vec![join_amount(number, currency.as_deref(), self.config)];
+        if let Some(cost) = posting.cost_spec.as_ref() {
+          parts.push(match CostSpec::parse(cost.raw.content) {
+            Some(mut spec) => {
+              spec.amount = spec
+                .amount
+                .map(|amount| normalize_cost_amount(&amount, self.config));
+              spec.to_string()
+            }
+            None => compact_ws(cost.raw.content),
+          });
+        }
+        // `@` and `@@` share one spacing before their price.
+        let operator = posting.price_operator.as_ref().map(|op| match op.content {
+          PriceOperator::PerUnit => "@",
+          PriceOperator::Total => "@@",
+        });
+        let price = posting
+          .price_annotation
+          .as_ref()
+          .map(|price| normalize_amount_text(price.raw.content, self.config));
+        match (operator, price) {
+          (Some(operator), Some(price)) => {
+            let spacing = " ".repeat(self.config.price_operator_spacing as usize);
+            parts.push(format!("{operator}{spacing}{price}"));
           }
-          Some(parts.join(" "))
-        } else {
-          None
-        };
+          // An operator is only written before a price, so a lone `@` never
+          // dangles at the end of the line; a lone price is kept as written.
+          (_, price) => parts.extend(price),
+        }
+        Some(parts.join(" "))
+      } else {
+        None
+      };
 
       let mut line = String::new();
-      line.push_str(&" ".repeat(min_indent));
-      if let Some(f) = flag {
-        line.push_str(f);
-        line.push(' ');
-      }
+      line.push_str(&posting_lead(flag, min_indent, self.config));
       line.push_str(account);
 
-      line = align_trailing(line, trailing, self.config.line_width as usize);
+      let trailing_len = trailing.as_ref().map(String::len);
 
-      if let Some(comment) = &posting.comment {
-        line = append_comment(line, &format_comment(comment), self.config, true);
+      // The currency column counts from the start of the line, so the indent
+      // and any posting flag are part of it: a flagged posting gets two fewer
+      // pad spaces and its currency still starts at the same column.
+      let currency_column = self.config.currency_column;
+      line = match (fixed_gap, currency_column, amount_column, amount, trailing) {
+        (Some(gap), _, _, _, Some(trailing)) => {
+          format!("{line}{}{trailing}", " ".repeat(gap))
+        }
+        (_, Some(column), _, Some((number, _)), Some(trailing)) => {
+          let lead = currency_lead(number, self.config);
+          align_at_currency_column(line, &trailing, lead, column as usize, self.config)
+        }
+        (_, None, Some(column), _, Some(trailing)) => {
+          align_at_column(line, &trailing, 0, column)
+        }
+        (_, _, _, _, trailing) => {
+          align_trailing(line, trailing, self.config.line_width as usize)
+        }
+      };
+
+      let tolerance = self.config.alignment_tolerance as usize;
+      if tolerance > 0
+        && fixed_gap.is_none()
+        && owns_line
+        && let (Some(len), Some(source_amount)) = (trailing_len, &posting.amount)
+      {
+        let offset = source_amount.raw.span.start - txn.span.start;
+        let line_start = txn_text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let source_prefix = &txn_text[line_start..offset];
+        let source_column = display_width(&expand_tabs_outside_strings(
+          source_prefix,
+          self.config.indent_width,
+        ));
+        line = keep_within_tolerance(line, len, source_column, tolerance);
       }
 
-      if let Some(slot) = lines.get_mut(line_idx) {
-        *slot = line;
+      let posting_line = match &posting.comment {
+        Some(comment) => {
+          let comment = format_comment(comment, self.config);
+          place_comment(line, &comment, self.config, true)
+        }
+        None => CommentedLine {
+          above: None,
+          line,
+          below: None,
+        },
+      };
+
+      if line_idx >= lines.len() {
+        continue;
+      }
+      if owns_line {
+        above[line_idx].extend(posting_line.above);
+        lines[line_idx] = posting_line.line;
+      } else {
+        moved[line_idx].extend(posting_line.above);
+        moved[line_idx].push(posting_line.line);
       }
+      moved[line_idx].extend(posting_line.below);
     }
 
+    // Metadata lines get canonical `key: value` spacing and, with
+    // `metadata_extra_indent`, are re-indented below the transaction or the
+    // posting they follow. Continuation lines of multi-line strings are content
+    // and stay as written.
+    let extra_indent = self.config.metadata_extra_indent as usize;
+    let indent_width = self.config.indent_width as usize;
+    let first_posting_line = posting_lines
+      .iter()
+      .filter(|(_, owns_line)| *owns_line)
+      .map(|(idx, _)| *idx)
+      .min();
+    let mut in_string = false;
     for (idx, line) in lines.iter_mut().enumerate().skip(1) {
-      if posting_line_indices.contains(&idx) {
+      let starts_in_string = in_string;
+      in_string ^= line.matches('"').count() % 2 == 1;
+      if posting_lines.contains(&(idx, true)) {
+        continue;
+      }
+      *line = normalize_indentation(line, self.config);
+      if starts_in_string {
+        continue;
+      }
+      // Comment-only lines are indented like the postings around them.
+      if line.trim_start().starts_with(';') {
+        let comment = format_comment_text(line, self.config);
+        *line = format!("{}{}", " ".repeat(min_indent), comment);
         continue;
       }
-      *line = normalize_indentation(line, self.config.indent_width);
+      let Some(body) = metadata_line(line) else {
+        continue;
+      };
+      let body = normalize_metadata_date(normalize_key_value(body), self.config);
+      let indent = if extra_indent > 0 {
+        let parent_indent = match first_posting_line {
+          Some(first) if idx > first => min_indent + indent_width,
+          _ => indent_width,
+        };
+        parent_indent + extra_indent
+      } else {
+        line.len() - line.trim_start().len()
+      };
+      *line = format!("{}{}", " ".repeat(indent), body);
+    }
+
+    let mut lines: Vec<(Vec<String>, String, Vec<String>)> = above
+      .into_iter()
+      .zip(lines)
+      .zip(moved)
+      .map(|((before, line), after)| (before, line, after))
+      .collect();
+    if self.config.sort_postings {
+      sort_posting_blocks(&mut lines, txn, &posting_lines);
+    }
+    let mut lines: Vec<String> = lines
+      .into_iter()
+      .flat_map(|(before, line, after)| {
+        before.into_iter().chain(std::iter::once(line)).chain(after)
+      })
+      .collect();
+    let continued = tag_link_lines(&lines);
+    let mut continued = continued.into_iter();
+    lines.retain(|_| !continued.next().unwrap_or(false));
+
+    if !self.config.preserve_posting_blank_lines {
+      // Metadata and comments attach to the posting above it and the next
+      // posting follows immediately, so blank lines in the body are dropped.
+      // Blank lines inside multi-line strings are content.
+      let mut in_string = false;
+      lines.retain(|line| {
+        let keep = in_string || !line.trim().is_empty();
+        in_string ^= line.matches('"').count() % 2 == 1;
+        keep
+      });
     }
 
     self.write(&lines.join("\n"));
   }
 
+  /// Payee and narration strings are kept verbatim unless the user opted into
+  /// collapsing repeated spaces, which alters their content.
+  fn format_header_string(&self, text: &WithSpan<&str>) -> String {
+    let text = text.content.trim();
+    if self.config.collapse_string_spaces {
+      collapse_spaces(text)
+    } else {
+      text.to_string()
+    }
+  }
+
   fn format_key_values(&mut self, key_values: &[ast::KeyValue<'a>], full_source: &str) {
     if key_values.is_empty() {
       return;
     }
 
-    let indent = " ".repeat(self.config.indent_width as usize);
+    let extra_indent = self.config.metadata_extra_indent as usize;
+    let indent = " ".repeat(self.config.indent_width as usize + extra_indent);
 
     for kv in key_values {
       self.write("\n");
 
       let slice = &full_source[kv.span.start..kv.span.end];
-      let mut text = normalize_indentation(slice, self.config.indent_width);
+      let mut text = normalize_indentation(slice, self.config);
       if text.ends_with('\n') {
         text.pop();
       }
 
-      if text.starts_with(char::is_whitespace) {
-        self.write(&text);
+      // Keep the source indentation unless `metadata_extra_indent` asks for a
+      // fixed one, but canonicalize the `key: value` spacing, including
+      // value-less keys written as `key:`.
+      let body = text.trim_start();
+      let lead = &text[..text.len() - body.len()];
+      self.write(if lead.is_empty() || extra_indent > 0 {
+        &indent
       } else {
-        self.write(&indent);
-        self.write(&text);
-      }
+        lead
+      });
+      self.write(&normalize_metadata_date(
+        normalize_key_value(body),
+        self.config,
+      ));
     }
   }
 }
 
+/// Formats `source_text` like a freshly built [`Formatter`] would, without a
+/// path in the error.
 pub fn format(source_text: &str, config: &Configuration) -> Result<String> {
-  format_content(source_text, config)
+  format_with_raw_formatters(source_text, config, &RawFormatters::default())
 }
 
-fn format_content(content: &str, formatting_config: &Configuration) -> Result<String> {
-  if content.trim().is_empty() {
-    return Ok(String::new());
+/// A formatter to keep around for many calls, e.g. in a server, together with
+/// the raw formatters it applies.
+///
+/// The parser is a plain function with no setup or state to cache, so a
+/// `Formatter` is cheap to build and is `Send` and `Sync`; wrap it in a mutex
+/// to share one instance between threads, or build one per thread.
+#[derive(Debug, Default)]
+pub struct Formatter {
+  raw_formatters: RawFormatters,
+}
+
+impl Formatter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A formatter that passes raw entries to `raw_formatters`, like
+  /// [`format_with_raw_formatters`].
+  pub fn with_raw_formatters(raw_formatters: RawFormatters) -> Self {
+    Self { raw_formatters }
+  }
+
+  /// Formats `text` like [`format`]. `path` only names the file in errors.
+  pub fn format(
+    &mut self,
+    path: &Path,
+    text: &str,
+    config: &Configuration,
+  ) -> Result<String> {
+    format_with_raw_formatters(text, config, &self.raw_formatters)
+      .with_context(|| format!("Failed to format {}", path.display()))
+  }
+}
+
+/// Like [`format`], but entries the parser returns as raw text are passed to
+/// the matching formatter of `raw_formatters`.
+pub fn format_with_raw_formatters(
+  source_text: &str,
+  config: &Configuration,
+  raw_formatters: &RawFormatters,
+) -> Result<String> {
+  let formatted = format_content(source_text, config, raw_formatters)?;
+  if config.verify_semantics {
+    verify_semantics(source_text, &formatted, config)?;
+  }
+  Ok(apply_trailing_newline(formatted, config))
+}
+
+/// Drops the final newline of a whole formatted file when `trailing_newline`
+/// is off. Only the last terminator goes; the lines before keep theirs.
+fn apply_trailing_newline(mut formatted: String, config: &Configuration) -> String {
+  if !config.trailing_newline {
+    let end = formatted
+      .strip_suffix("\r\n")
+      .or_else(|| formatted.strip_suffix('\n'))
+      .map_or(formatted.len(), str::len);
+    formatted.truncate(end);
+  }
+  formatted
+}
+
+/// Why [`format_with_diagnostics`] failed.
+#[derive(Debug)]
+pub enum FormatError {
+  /// Parts of the source could not be parsed; every one of them is listed.
+  Syntax(Vec<SyntaxError>),
+  /// Formatting failed for another reason, e.g. semantic verification.
+  Other(anyhow::Error),
+}
+
+impl Display for FormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FormatError::Syntax(errors) => {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        write!(f, "syntax errors: {}", errors.join("; "))
+      }
+      FormatError::Other(err) => write!(f, "{err}"),
+    }
+  }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Like [`format`], but fails with the position of every part of the source
+/// the parser could not read instead of keeping them as written, so editors
+/// can underline them. See [`syntax_errors`] for what is detected.
+pub fn format_with_diagnostics(
+  source_text: &str,
+  config: &Configuration,
+) -> Result<String, FormatError> {
+  let errors = syntax_errors(source_text);
+  if !errors.is_empty() {
+    return Err(FormatError::Syntax(errors));
+  }
+  format(source_text, config).map_err(FormatError::Other)
+}
+
+/// Formats a concatenation of ledgers whose boundaries are lines starting with
+/// `boundary_marker` (e.g. `; ===== file:`). Each section between markers is
+/// formatted on its own and the marker lines are kept byte-for-byte.
+pub fn format_sections(
+  source_text: &str,
+  config: &Configuration,
+  boundary_marker: &str,
+) -> Result<String> {
+  let raw_formatters = RawFormatters::default();
+  let mut out = String::with_capacity(source_text.len());
+  let mut section_start = 0;
+  let mut offset = 0;
+  for line in source_text.split_inclusive('\n') {
+    if line.starts_with(boundary_marker) {
+      let section = &source_text[section_start..offset];
+      out.push_str(&format_content(section, config, &raw_formatters)?);
+      out.push_str(line);
+      section_start = offset + line.len();
+    }
+    offset += line.len();
+  }
+  let section = &source_text[section_start..];
+  out.push_str(&format_content(section, config, &raw_formatters)?);
+  // Marker lines are comments, so the whole output can be checked at once.
+  if config.verify_semantics {
+    verify_semantics(source_text, &out, config)?;
+  }
+  Ok(apply_trailing_newline(out, config))
+}
+
+/// Formats the directives overlapping the byte `range` of `source_text`. The
+/// range is first widened to whole directives, from the start of the first
+/// one's line to the end of the last one's line, so a selection never splits a
+/// transaction. Returns the widened range and its formatted replacement; a
+/// range touching no directive comes back unchanged. A range reaching past the
+/// end of `source_text` or splitting a character is an error.
+pub fn format_range(
+  source_text: &str,
+  range: Range<usize>,
+  config: &Configuration,
+) -> Result<(Range<usize>, String)> {
+  if range.start > range.end
+    || range.end > source_text.len()
+    || !source_text.is_char_boundary(range.start)
+    || !source_text.is_char_boundary(range.end)
+  {
+    anyhow::bail!(
+      "range {}..{} is not a valid selection of the {} byte source",
+      range.start,
+      range.end,
+      source_text.len()
+    );
   }
+  let content = with_trailing_newline(source_text);
+  // An empty range is a cursor and selects the directive it is in.
+  let range_end = range.end.max(range.start + 1);
+  let spans: Vec<ast::Span> = parse_source(&content)
+    .iter()
+    .map(directive_span)
+    .filter(|span| span.start < range_end && range.start < span.end)
+    .collect();
+  let (Some(start), Some(end)) = (
+    spans.iter().map(|span| span.start).min(),
+    spans
+      .iter()
+      .map(|span| span.end.min(source_text.len()))
+      .max(),
+  ) else {
+    return Ok((range.clone(), source_text[range].to_string()));
+  };
 
-  // The parser expects a trailing newline; append one if it's missing.
-  let content = if content.ends_with('\n') || content.ends_with("\r\n") {
-    content.to_string()
+  let start = source_text[..start].rfind('\n').map_or(0, |idx| idx + 1);
+  let end = if source_text[..end].ends_with('\n') {
+    end
   } else {
-    format!("{}\n", content)
+    source_text[end..]
+      .find('\n')
+      .map_or(source_text.len(), |idx| end + idx + 1)
   };
+  let selected = &source_text[start..end];
+  let mut formatted = format_content(selected, config, &RawFormatters::default())?;
+  if config.verify_semantics {
+    verify_semantics(selected, &formatted, config)?;
+  }
+  // Only the end of the file loses its newline, like with `format`.
+  if end == source_text.len() {
+    formatted = apply_trailing_newline(formatted, config);
+  }
+  Ok((start..end, formatted))
+}
+
+fn format_content(
+  content: &str,
+  formatting_config: &Configuration,
+  raw_formatters: &RawFormatters,
+) -> Result<String> {
+  if content.trim().is_empty() {
+    return Ok(String::new());
+  }
+
+  let content = with_trailing_newline(content);
 
   let directives = parse_source(&content);
 
-  let newline = match formatting_config.new_line {
+  for dir in directives.iter() {
+    if let Directive::Price(d) = dir {
+      check_price_currency(d, &content)?;
+    }
+  }
+
+  let newline = match formatting_config.new_line_kind {
     NewLineKind::LF => "\n",
     NewLineKind::CRLF => "\r\n",
   };
 
-  let mut ctx = FormatterContext::new(formatting_config, content.len());
-  let mut prev_end_line: Option<usize> = None;
+  let layouts = compute_run_layouts(&directives, &content, formatting_config);
+
+  // Blank lines written in the source before each directive. A directive moved
+  // by sorting keeps the spacing it had at its original position.
+  let mut source_gaps = vec![0; directives.len()];
+  for idx in 1..directives.len() {
+    let prev_end = directive_end_line(&directives[idx - 1], &content);
+    let start_line = directive_start_line(&directives[idx], &content);
+    source_gaps[idx] = start_line.saturating_sub(prev_end + 1);
+  }
+
+  let order = directive_order(&directives, &content, formatting_config);
+
+  let hinted_configs =
+    hinted_configs(&directives, &content, &source_gaps, formatting_config);
+
+  let mut ctx = FormatterContext::new(formatting_config, raw_formatters, content.len());
+  if formatting_config.global_account_alignment {
+    ctx.file_amount_column =
+      file_amount_column(&directives, &content, formatting_config);
+  }
+  let max_blank_lines = formatting_config.max_blank_lines as usize;
+  if formatting_config.preserve_leading_blank_lines
+    && let Some(first) = directives.first()
+  {
+    let leading_blank_lines = directive_start_line(first, &content) - 1;
+    for _ in 0..leading_blank_lines.min(max_blank_lines) {
+      ctx.write(newline);
+    }
+  }
+
+  let mut is_first = true;
   let mut prev_is_txn = false;
   let mut prev_is_balance = false;
   let mut prev_is_comment = false;
 
-  for dir in directives.iter() {
+  for idx in order {
+    let dir = &directives[idx];
     let is_txn = matches!(dir, Directive::Transaction(_));
     let is_balance = matches!(dir, Directive::Balance(_));
     let is_comment = matches!(dir, Directive::Comment(_));
-    if let Some(prev_end) = prev_end_line {
-      let start_line = directive_start_line(dir, &content);
-      let mut blank_lines = start_line.saturating_sub(prev_end + 1).min(2);
+    if !is_first {
+      let mut blank_lines = source_gaps[idx].min(max_blank_lines);
       if formatting_config.compact_balance_spacing && prev_is_balance && is_balance {
         blank_lines = 0;
       }
-      // preserve at least one and at most 2 empty lines whenever a transaction is involved
-      let txn_min = if (prev_is_txn || is_txn) && !(prev_is_comment && is_txn) {
-        1
+      // preserve at least one empty line whenever a transaction is involved
+      let txn_min = if formatting_config.blank_lines == BlankLines::Normalize
+        && formatting_config.blank_lines_around_transactions
+        && (prev_is_txn || is_txn)
+        && !(prev_is_comment && is_txn)
+      {
+        max_blank_lines.min(1)
       } else {
         0
       };
@@ -507,10 +1171,12 @@ fn format_content(content: &str, formatting_config: &Configuration) -> Result<St
       }
     }
 
+    ctx.layout = layouts[idx];
+    ctx.config = hinted_configs[idx].as_ref().unwrap_or(formatting_config);
     ctx.format_directive(dir, &content);
     ctx.write(newline);
 
-    prev_end_line = Some(directive_end_line(dir, &content));
+    is_first = false;
     prev_is_txn = is_txn;
     prev_is_balance = is_balance;
     prev_is_comment = is_comment;
@@ -548,8 +1214,95 @@ fn format_content(content: &str, formatting_config: &Configuration) -> Result<St
   Ok(formatted)
 }
 
+/// A `; col:N` comment line right above a directive overrides the currency
+/// column for that directive alone.
+fn hinted_configs(
+  directives: &[Directive<'_>],
+  content: &str,
+  source_gaps: &[usize],
+  config: &Configuration,
+) -> Vec<Option<Configuration>> {
+  (0..directives.len())
+    .map(|idx| {
+      if !config.respect_column_hints || idx == 0 || source_gaps[idx] > 0 {
+        return None;
+      }
+      let Directive::Comment(comment) = &directives[idx - 1] else {
+        return None;
+      };
+      column_hint(&content[comment.span.start..comment.span.end]).map(|column| {
+        Configuration {
+          currency_column: Some(column),
+          ..config.clone()
+        }
+      })
+    })
+    .collect()
+}
+
+/// Formats every top-level entry of `source` on its own, comment lines
+/// included, and returns each one keyed by its byte range in `source`, in
+/// source order. Column alignment is the same as [`format`] produces, but no
+/// blank lines are added and nothing is reordered; each fragment ends with a
+/// newline. `path` only names the file in errors.
+pub fn format_each(
+  path: &Path,
+  source: &str,
+  config: &Configuration,
+) -> Result<Vec<(Range<usize>, String)>> {
+  let content = with_trailing_newline(source);
+  let directives = parse_source(&content);
+  for dir in directives.iter() {
+    if let Directive::Price(d) = dir {
+      check_price_currency(d, &content)
+        .with_context(|| format!("Failed to format {}", path.display()))?;
+    }
+  }
+
+  let layouts = compute_run_layouts(&directives, &content, config);
+  let mut source_gaps = vec![0; directives.len()];
+  for idx in 1..directives.len() {
+    let prev_end = directive_end_line(&directives[idx - 1], &content);
+    let start_line = directive_start_line(&directives[idx], &content);
+    source_gaps[idx] = start_line.saturating_sub(prev_end + 1);
+  }
+  let hinted_configs = hinted_configs(&directives, &content, &source_gaps, config);
+  let file_amount_column = if config.global_account_alignment {
+    file_amount_column(&directives, &content, config)
+  } else {
+    None
+  };
+  let raw_formatters = RawFormatters::default();
+  let newline = match config.new_line_kind {
+    NewLineKind::LF => "\n",
+    NewLineKind::CRLF => "\r\n",
+  };
+
+  let fragments = directives
+    .iter()
+    .enumerate()
+    .map(|(idx, dir)| {
+      let span = directive_span(dir);
+      let capacity = span.end - span.start;
+      let mut ctx = FormatterContext::new(config, &raw_formatters, capacity);
+      ctx.file_amount_column = file_amount_column;
+      ctx.layout = layouts[idx];
+      ctx.config = hinted_configs[idx].as_ref().unwrap_or(config);
+      ctx.format_directive(dir, &content);
+      let mut fragment = ctx
+        .finish()
+        .replace("\r\n", "\n")
+        .trim_end_matches('\n')
+        .replace('\n', newline);
+      fragment.push_str(newline);
+      (span.start..span.end.min(source.len()), fragment)
+    })
+    .collect();
+  Ok(fragments)
+}
+
 /// Normalizes tabs to spaces (respecting indent width) outside of string literals and trims trailing whitespace per line.
-fn normalize_indentation(text: &str, indent_width: u8) -> String {
+fn normalize_indentation(text: &str, config: &Configuration) -> String {
   let mut out = String::with_capacity(text.len());
 
   for (i, line) in text.replace("\r\n", "\n").lines().enumerate() {
@@ -558,14 +1311,44 @@ fn normalize_indentation(text: &str, indent_width: u8) -> String {
     }
 
     // Expand tabs outside of string literals, then trim trailing whitespace.
-    let expanded = expand_tabs_outside_strings(line, indent_width);
-    let trimmed = expanded.trim_end();
-    out.push_str(trimmed);
+    // Trailing whitespace of a line ending in a comment belongs to the comment
+    // and is kept when the user opted out of trimming it.
+    let expanded = expand_tabs_outside_strings(line, config.indent_width);
+    if !config.trim_comment_trailing && comment_start(&expanded).is_some() {
+      out.push_str(&expanded);
+    } else {
+      out.push_str(expanded.trim_end());
+    }
   }
 
   out
 }
 
+/// Byte offset of the `;` starting a comment on `line`, ignoring semicolons
+/// inside string literals.
+fn comment_start(line: &str) -> Option<usize> {
+  let mut in_string = false;
+  let mut escape = false;
+  for (idx, ch) in line.char_indices() {
+    if in_string {
+      if escape {
+        escape = false;
+      } else if ch == '\\' {
+        escape = true;
+      } else if ch == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    match ch {
+      '"' => in_string = true,
+      ';' => return Some(idx),
+      _ => {}
+    }
+  }
+  None
+}
+
 /// Expand tabs to spaces while skipping tabs that appear inside string literals.
 /// Leading tabs expand to the configured indent width; tabs elsewhere become a single space.
 fn expand_tabs_outside_strings(line: &str, indent_width: u8) -> String {
@@ -614,6 +1397,108 @@ fn expand_tabs_outside_strings(line: &str, indent_width: u8) -> String {
   out
 }
 
+/// Replaces each full `indent_width` run of leading spaces with a tab. Lines
+/// that start inside a multi-line string literal are left untouched.
+fn tabify_indentation(text: &str, indent_width: u8) -> String {
+  let indent_width = (indent_width as usize).max(1);
+  let mut out = String::with_capacity(text.len());
+  let mut in_string = false;
+
+  for (i, line) in text.split('\n').enumerate() {
+    if i > 0 {
+      out.push('\n');
+    }
+
+    let mut rest = line;
+    if !in_string {
+      let spaces = line.len() - line.trim_start_matches(' ').len();
+      let levels = spaces / indent_width;
+      out.push_str(&"\t".repeat(levels));
+      rest = &line[levels * indent_width..];
+    }
+    out.push_str(rest);
+
+    let mut escape = false;
+    for ch in line.chars() {
+      if escape {
+        escape = false;
+      } else if in_string && ch == '\\' {
+        escape = true;
+      } else if ch == '"' {
+        in_string = !in_string;
+      } else if ch == ';' && !in_string {
+        break;
+      }
+    }
+  }
+
+  out
+}
+
+/// Moves the `trailing_len` bytes ending `line` back to `source_column` when
+/// that is within `tolerance` of where alignment put them, as long as at least
+/// one space still separates them from the text before.
+fn keep_within_tolerance(
+  line: String,
+  trailing_len: usize,
+  source_column: usize,
+  tolerance: usize,
+) -> String {
+  let target = line.len() - trailing_len;
+  let base = line[..target].trim_end();
+  let target_column = display_width(&line[..target]);
+  let base_width = display_width(base);
+  if source_column.abs_diff(target_column) > tolerance || source_column <= base_width {
+    return line;
+  }
+  format!(
+    "{}{}{}",
+    base,
+    " ".repeat(source_column - base_width),
+    &line[target..]
+  )
+}
+
+/// The text of `line` without its indentation if it is a `key: value` metadata
+/// line.
+fn metadata_line(line: &str) -> Option<&str> {
+  let body = line.trim_start();
+  let (key, _) = body.split_once(':')?;
+  is_metadata_key(key).then_some(body)
+}
+
+/// The column of a `; col:N` hint comment. Anything else, including a column
+/// that isn't a positive number, is not a hint.
+fn column_hint(comment: &str) -> Option<u32> {
+  let column = comment
+    .trim()
+    .strip_prefix(';')?
+    .trim_start()
+    .strip_prefix("col:")?
+    .trim()
+    .parse()
+    .ok()?;
+  (column > 0).then_some(column)
+}
+
+/// A price amount must name its currency; formatting one without it would emit
+/// a directive that beancount rejects, so report it instead.
+fn check_price_currency(d: &ast::Price<'_>, text: &str) -> Result<()> {
+  let has_currency = d
+    .amount
+    .currency
+    .as_ref()
+    .is_some_and(|c| !c.content.trim().is_empty());
+  if !has_currency {
+    let (line, column) = line_col_at_offset(text, d.amount.raw.span.start);
+    anyhow::bail!(
+      "price directive at line {line}, column {column} has an amount without a currency: `{}`",
+      d.amount.raw.content.trim()
+    );
+  }
+  Ok(())
+}
+
 fn count_newlines_up_to(text: &str, offset: usize) -> usize {
   text
     .as_bytes()
@@ -623,7 +1508,7 @@ fn count_newlines_up_to(text: &str, offset: usize) -> usize {
     .count()
 }
 
-fn directive_span(dir: &Directive<'_>) -> ast::Span {
+pub(crate) fn directive_span(dir: &Directive<'_>) -> ast::Span {
   match dir {
     Directive::Open(d) => d.span,
     Directive::Close(d) => d.span,
@@ -654,6 +1539,14 @@ fn line_at_offset(text: &str, offset: usize) -> usize {
   count_newlines_up_to(text, offset) + 1
 }
 
+/// 1-based line and column (in characters) of a byte offset.
+pub(crate) fn line_col_at_offset(text: &str, offset: usize) -> (usize, usize) {
+  let offset = offset.min(text.len());
+  let line_start = text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+  let column = text[line_start..offset].chars().count() + 1;
+  (line_at_offset(text, offset), column)
+}
+
 fn directive_start_line(dir: &Directive<'_>, text: &str) -> usize {
   let span = directive_span(dir);
   line_at_offset(text, span.start)
@@ -665,6 +1558,235 @@ fn directive_end_line(dir: &Directive<'_>, text: &str) -> usize {
   line_at_offset(text, end_offset)
 }
 
+/// Returns the indices of `directives` in the order they should be written.
+///
+/// With `sort_by_date`, dated directives are stably sorted by date within each
+/// section delimited by undated directives and standalone comments. Comments
+/// directly above a dated directive travel with it, or directly below it with
+/// `CommentAnchoring::Preceding`. `sort_directives` implies `sort_by_date` and
+/// also moves `option`, `plugin` and `include` to the top first, so they no
+/// longer delimit sections.
+fn directive_order(
+  directives: &[Directive<'_>],
+  text: &str,
+  config: &Configuration,
+) -> Vec<usize> {
+  if !(config.sort_by_date || config.sort_directives) || config.minimal {
+    return (0..directives.len()).collect();
+  }
+
+  let is_comment = |idx: usize| matches!(directives[idx], Directive::Comment(_));
+  let touches_next = |idx: usize| {
+    idx + 1 < directives.len()
+      && directive_start_line(&directives[idx + 1], text)
+        == directive_end_line(&directives[idx], text) + 1
+  };
+
+  // Each group is a directive's index and the index range including the
+  // comments anchored to it.
+  let mut groups: Vec<(usize, Range<usize>)> = Vec::new();
+  let mut idx = 0;
+  while idx < directives.len() {
+    let mut end = idx;
+    let key = match config.comment_anchoring {
+      CommentAnchoring::Following => {
+        while is_comment(end) && touches_next(end) {
+          end += 1;
+        }
+        end
+      }
+      CommentAnchoring::Preceding => {
+        if !is_comment(idx) {
+          while touches_next(end) && is_comment(end + 1) {
+            end += 1;
+          }
+        }
+        idx
+      }
+    };
+    groups.push((key, idx..end + 1));
+    idx = end + 1;
+  }
+
+  let mut order = Vec::with_capacity(directives.len());
+  if config.sort_directives {
+    let (pinned, rest): (Vec<_>, Vec<_>) = groups.into_iter().partition(|(idx, _)| {
+      matches!(
+        directives[*idx],
+        Directive::Option(_) | Directive::Plugin(_) | Directive::Include(_)
+      )
+    });
+    for (_, range) in pinned {
+      order.extend(range);
+    }
+    groups = rest;
+  }
+
+  // Sortable groups of the current section.
+  let mut section: Vec<(usize, Range<usize>)> = Vec::new();
+  for (idx, range) in groups {
+    if directive_date(&directives[idx]).is_some() {
+      section.push((idx, range));
+    } else {
+      flush_sorted_section(&mut order, &mut section, directives, config);
+      order.extend(range);
+    }
+  }
+  flush_sorted_section(&mut order, &mut section, directives, config);
+  order
+}
+
+fn flush_sorted_section(
+  order: &mut Vec<usize>,
+  section: &mut Vec<(usize, Range<usize>)>,
+  directives: &[Directive<'_>],
+  config: &Configuration,
+) {
+  let key = |idx: usize| {
+    let dir = &directives[idx];
+    let date = directive_date(dir).unwrap_or_default().replace('/', "-");
+    let priority = match config.same_date_order {
+      SameDateOrder::Source => 0,
+      SameDateOrder::TypePriority => type_priority(dir),
+    };
+    (date, priority)
+  };
+  // `sort_by_cached_key` is stable, so ties keep their source order.
+  section.sort_by_cached_key(|(idx, _)| key(*idx));
+  for (_, range) in section.drain(..) {
+    order.extend(range);
+  }
+}
+
+/// Beancount's ordering of directives sharing a date.
+fn type_priority(dir: &Directive<'_>) -> i8 {
+  match dir {
+    Directive::Open(_) => -2,
+    Directive::Balance(_) => -1,
+    Directive::Document(_) => 1,
+    Directive::Close(_) => 2,
+    _ => 0,
+  }
+}
+
+fn directive_date<'a>(dir: &Directive<'a>) -> Option<&'a str> {
+  directive_date_span(dir).map(|date| date.content.trim())
+}
+
+/// The date of a dated directive, with its position in the source.
+pub(crate) fn directive_date_span<'d, 'a>(
+  dir: &'d Directive<'a>,
+) -> Option<&'d WithSpan<&'a str>> {
+  let date = match dir {
+    Directive::Open(d) => &d.date,
+    Directive::Close(d) => &d.date,
+    Directive::Balance(d) => &d.date,
+    Directive::Pad(d) => &d.date,
+    Directive::Transaction(d) => &d.date,
+    Directive::Commodity(d) => &d.date,
+    Directive::Price(d) => &d.date,
+    Directive::Event(d) => &d.date,
+    Directive::Query(d) => &d.date,
+    Directive::Note(d) => &d.date,
+    Directive::Document(d) => &d.date,
+    Directive::Custom(d) => &d.date,
+    _ => return None,
+  };
+  Some(date)
+}
+
+/// Computes the shared column layout for every directive, based on the blocks
+/// of consecutive directives it belongs to.
+fn compute_run_layouts(
+  directives: &[Directive<'_>],
+  text: &str,
+  config: &Configuration,
+) -> Vec<RunLayout> {
+  let mut layouts = vec![RunLayout::default(); directives.len()];
+  if config.align_keyword {
+    let is_single_operand =
+      |dir: &Directive<'_>| single_operand_prefix(dir, config).is_some();
+    for run in contiguous_runs(directives, text, is_single_operand) {
+      let width = directives[run.clone()]
+        .iter()
+        .filter_map(|dir| single_operand_prefix(dir, config))
+        .map(|prefix| display_width(&prefix))
+        .max()
+        .unwrap_or(0);
+      for layout in &mut layouts[run] {
+        layout.operand_column = Some(width + 2);
+      }
+    }
+  }
+  if config.align_text_directives {
+    let is_event = |dir: &Directive<'_>| matches!(dir, Directive::Event(_));
+    for run in contiguous_runs(directives, text, is_event) {
+      let width = directives[run.clone()]
+        .iter()
+        .filter_map(|dir| match dir {
+          Directive::Event(d) => Some(display_width(&event_prefix(d, config))),
+          _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+      for layout in &mut layouts[run] {
+        layout.text_column = Some(width + 2);
+      }
+    }
+  }
+  if config.align_custom {
+    let is_custom = |dir: &Directive<'_>| matches!(dir, Directive::Custom(_));
+    for run in contiguous_runs(directives, text, is_custom) {
+      let width = directives[run.clone()]
+        .iter()
+        .filter_map(|dir| match dir {
+          Directive::Custom(d) if !d.values.is_empty() => {
+            Some(display_width(&custom_prefix(d, config)))
+          }
+          _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+      for layout in &mut layouts[run] {
+        layout.values_column = Some(width + 2);
+      }
+    }
+  }
+  layouts
+}
+
+/// Index ranges of directives matching `include` that directly follow each
+/// other in the source, with no blank line or other directive in between.
+fn contiguous_runs(
+  directives: &[Directive<'_>],
+  text: &str,
+  include: impl Fn(&Directive<'_>) -> bool,
+) -> Vec<Range<usize>> {
+  let mut runs = Vec::new();
+  let mut start: Option<usize> = None;
+  for (idx, dir) in directives.iter().enumerate() {
+    if !include(dir) {
+      if let Some(run_start) = start.take() {
+        runs.push(run_start..idx);
+      }
+      continue;
+    }
+    if let Some(run_start) = start {
+      let prev_end = directive_end_line(&directives[idx - 1], text);
+      if directive_start_line(dir, text) > prev_end + 1 {
+        runs.push(run_start..idx);
+        start = Some(idx);
+      }
+    } else {
+      start = Some(idx);
+    }
+  }
+  if let Some(run_start) = start {
+    runs.push(run_start..directives.len());
+  }
+  runs
+}
+
 fn leading_indent_width(line: &str, indent_width: u8) -> usize {
   let mut width = 0usize;
   for ch in line.chars() {
@@ -687,24 +1809,129 @@ fn join_parts(parts: impl IntoIterator<Item = Option<String>>) -> String {
   out.join(" ")
 }
 
+/// Columns `text` takes up in a terminal: wide (e.g. CJK) characters count
+/// twice and combining marks not at all.
+fn display_width(text: &str) -> usize {
+  text.width()
+}
+
 fn to_part(text: &WithSpan<&str>) -> String {
   text.content.trim().to_string()
 }
 
-fn compact_ws(text: &str) -> String {
+/// Writes a `YYYY-MM-DD`, `YYYY/MM/DD` or `YYYY.MM.DD` date with the configured
+/// separator.
+/// Anything else is kept as written.
+fn format_date(date: &WithSpan<&str>, config: &Configuration) -> String {
+  let text = date.content.trim();
+  normalize_date(text, config).unwrap_or_else(|| text.to_string())
+}
+
+/// `text` written with `date_separator`, if it is a well-formed date.
+pub(crate) fn normalize_date(text: &str, config: &Configuration) -> Option<String> {
+  let (year, month, day) = date_parts(text)?;
+  let sep = config.date_separator.as_char();
+  Some(format!("{year}{sep}{month}{sep}{day}"))
+}
+
+/// A `custom` value written with `date_separator` when it is a date and
+/// `normalize_metadata_dates` is set. Strings and other values are kept.
+pub(crate) fn normalize_value_date(value: &str, config: &Configuration) -> String {
+  if config.normalize_metadata_dates
+    && let Some(date) = normalize_date(value, config)
+  {
+    return date;
+  }
+  value.to_string()
+}
+
+/// A canonical metadata `key: value` line whose value, up to any comment, is
+/// passed through [`normalize_value_date`].
+fn normalize_metadata_date(key_value: String, config: &Configuration) -> String {
+  if !config.normalize_metadata_dates {
+    return key_value;
+  }
+  let Some((key, value)) = key_value.split_once(": ") else {
+    return key_value;
+  };
+  let end = value.find(char::is_whitespace).unwrap_or(value.len());
+  let (date, rest) = value.split_at(end);
+  format!("{key}: {}{rest}", normalize_value_date(date, config))
+}
+
+pub(crate) fn compact_ws(text: &str) -> String {
   text.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
+pub(crate) fn collapse_spaces(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut prev_space = false;
+  for ch in text.chars() {
+    if ch == ' ' && prev_space {
+      continue;
+    }
+    prev_space = ch == ' ';
+    out.push(ch);
+  }
+  out
+}
+
+/// Removes whitespace after a leading sign and drops a redundant leading `+`.
 fn normalize_sign_spacing(number: &str) -> String {
   if let Some(rest) = number.strip_prefix('-') {
     format!("-{}", rest.trim_start())
-  } else if let Some(rest) = number.strip_prefix('+') {
-    format!("+{}", rest.trim_start())
+  } else if let Some(rest) = number.strip_prefix('+')
+    && !rest.trim_start().is_empty()
+  {
+    rest.trim_start().to_string()
   } else {
     number.to_string()
   }
 }
 
+/// Writes an arithmetic number expression with one space around binary
+/// operators and none after unary signs or inside parentheses, e.g.
+/// `(10+5) *2` as `(10 + 5) * 2`. Anything else is only whitespace-compacted.
+fn normalize_expression(expr: &str) -> String {
+  let mut out = String::with_capacity(expr.len());
+  // Whether the previous token ends an operand, making a following `+`/`-` a
+  // binary operator.
+  let mut after_operand = false;
+  let fallback = || normalize_sign_spacing(&compact_ws(expr));
+  let is_number = |c: char| c.is_ascii_digit() || c == '.' || c == ',';
+  let mut chars = expr.chars().peekable();
+  while let Some(ch) = chars.next() {
+    match ch {
+      c if c.is_whitespace() => {}
+      // Two operands in a row aren't an expression this understands.
+      c if (is_number(c) || c == '(') && after_operand => return fallback(),
+      c if is_number(c) => {
+        out.push(c);
+        while let Some(&next) = chars.peek().filter(|&&next| is_number(next)) {
+          out.push(next);
+          chars.next();
+        }
+        after_operand = true;
+      }
+      '+' | '-' | '*' | '/' if after_operand => {
+        out.push(' ');
+        out.push(ch);
+        out.push(' ');
+        after_operand = false;
+      }
+      '-' => out.push('-'),
+      '+' => {}
+      '(' => out.push('('),
+      ')' => {
+        out.push(')');
+        after_operand = true;
+      }
+      _ => return fallback(),
+    }
+  }
+  out
+}
+
 fn number_text_from_amount(amount: &ast::Amount<'_>) -> String {
   match &amount.number {
     ast::NumberExpr::Literal(value) => {
@@ -715,7 +1942,7 @@ fn number_text_from_amount(amount: &ast::Amount<'_>) -> String {
       let start = span.start.saturating_sub(amount.raw.span.start);
       let end = span.end.saturating_sub(amount.raw.span.start);
       if start <= end && end <= raw.len() {
-        raw[start..end].to_string()
+        normalize_expression(&raw[start..end])
       } else {
         raw.to_string()
       }
@@ -734,15 +1961,72 @@ fn normalize_key_value(text: &str) -> String {
   }
 }
 
+/// A line and its comment. `long_comment_placement` can move a comment that
+/// doesn't fit onto a line of its own above or below.
+struct CommentedLine {
+  above: Option<String>,
+  line: String,
+  below: Option<String>,
+}
+
 fn append_comment(
-  mut line: String,
+  line: String,
   comment: &str,
   config: &Configuration,
   align: bool,
 ) -> String {
+  let CommentedLine { above, line, below } =
+    place_comment(line, comment, config, align);
+  above
+    .into_iter()
+    .chain(std::iter::once(line))
+    .chain(below)
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn place_comment(
+  mut line: String,
+  comment: &str,
+  config: &Configuration,
+  align: bool,
+) -> CommentedLine {
   let trimmed = line.trim_end().to_string();
-  let base_len = trimmed.len();
-  let target = config.line_width as usize;
+  let base_len = display_width(&trimmed);
+  if !comment_fits(&trimmed, comment, config) {
+    let indent = &trimmed[..trimmed.len() - trimmed.trim_start().len()];
+    match config.long_comment_placement {
+      LongCommentPlacement::Trailing => {}
+      LongCommentPlacement::Above => {
+        return CommentedLine {
+          above: Some(format!("{indent}{comment}")),
+          line: trimmed,
+          below: None,
+        };
+      }
+      LongCommentPlacement::Below => {
+        // A comment at column 0 would end the directive and orphan the
+        // metadata or postings after it, so one below a top-level line is
+        // indented like them.
+        let indent = if indent.is_empty() {
+          " ".repeat(config.indent_width as usize)
+        } else {
+          indent.to_string()
+        };
+        return CommentedLine {
+          above: None,
+          below: Some(format!("{indent}{comment}")),
+          line: trimmed,
+        };
+      }
+    }
+  }
+  // `comment_column` puts every inline comment at the same 1-based column,
+  // whatever the directive.
+  let (align, target) = match config.comment_column {
+    Some(column) => (true, (column as usize).saturating_sub(1)),
+    None => (align, config.line_width as usize),
+  };
 
   line = trimmed;
   if align && base_len < target {
@@ -752,22 +2036,36 @@ fn append_comment(
   }
 
   line.push_str(comment);
-  line
+  CommentedLine {
+    above: None,
+    line,
+    below: None,
+  }
 }
 
+/// Whether `comment` fits within `line_width` after `line` and one space.
+fn comment_fits(line: &str, comment: &str, config: &Configuration) -> bool {
+  display_width(line.trim_end()) + 1 + display_width(comment)
+    <= config.line_width as usize
+}
+
+/// Right-aligns `trailing` so it ends two columns before `comment_col`. When
+/// `base` is too long for that, `trailing` follows it after exactly one space
+/// and the line overflows instead.
 fn align_trailing(
   mut base: String,
   trailing: Option<String>,
   comment_col: usize,
 ) -> String {
   if let Some(value) = trailing {
-    let value_len = value.len();
+    let value_len = display_width(&value);
     let target_end = comment_col.saturating_sub(2);
     let desired_start = target_end.saturating_sub(value_len.saturating_sub(1));
-    let start = desired_start.max(base.len().saturating_add(1));
+    let base_len = display_width(&base);
+    let start = desired_start.max(base_len.saturating_add(1));
 
-    if base.len() < start {
-      base.push_str(&" ".repeat(start - base.len()));
+    if base_len < start {
+      base.push_str(&" ".repeat(start - base_len));
     }
     base.push_str(&value);
   }
@@ -775,57 +2073,419 @@ fn align_trailing(
   base
 }
 
-fn format_amount(amount: &ast::Amount<'_>) -> Option<String> {
-  let number_text = number_text_from_amount(amount);
+/// The source line of each posting within `txn_text`, whether the posting owns
+/// that line, and the smallest indent among postings that do.
+///
+/// A posting owns its line when nothing but indentation precedes it. Postings
+/// sharing a line with the header or an earlier posting are moved onto a new
+/// line of their own right below it.
+fn posting_lines(
+  txn: &ast::Transaction<'_>,
+  txn_text: &str,
+  config: &Configuration,
+) -> (Vec<(usize, bool)>, usize) {
+  let mut lines = Vec::new();
+  let mut min_indent = usize::MAX;
+
+  for posting in &txn.postings {
+    let offset = posting.span.start.saturating_sub(txn.span.start);
+    let line_idx = count_newlines_up_to(txn_text, offset);
+    let line_start = txn_text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+    let owns_line = line_idx > 0 && txn_text[line_start..offset].trim().is_empty();
+    lines.push((line_idx, owns_line));
+    if owns_line {
+      // With `flag_in_margin` the indentation is where the account starts.
+      let indent = if config.flag_in_margin && posting.opt_flag.is_some() {
+        let account = posting.account.content;
+        let account_offset = posting.account.span.start - txn.span.start
+          + (account.len() - account.trim_start().len());
+        let prefix = &txn_text[line_start..account_offset];
+        expand_tabs_outside_strings(prefix, config.indent_width).len()
+      } else {
+        leading_indent_width(&txn_text[line_start..], config.indent_width)
+      };
+      min_indent = min_indent.min(indent);
+    }
+  }
+
+  if min_indent == usize::MAX {
+    min_indent = config.indent_width as usize;
+  }
+  (lines, min_indent)
+}
+
+/// Width of the widest posting with an amount, counting indent, flag and account.
+fn widest_posting_prefix(
+  txn: &ast::Transaction<'_>,
+  min_indent: usize,
+  config: &Configuration,
+) -> Option<usize> {
+  txn
+    .postings
+    .iter()
+    .filter(|posting| posting.amount.is_some())
+    .map(|posting| {
+      let flag = posting.opt_flag.as_ref().map(|flag| flag.content.trim());
+      let account = posting.account.content.trim();
+      posting_lead(flag, min_indent, config).len() + display_width(account)
+    })
+    .max()
+}
+
+/// The 1-based column where amounts start with `global_account_alignment`:
+/// two spaces after the widest posting with an amount anywhere in the file.
+fn file_amount_column(
+  directives: &[Directive<'_>],
+  content: &str,
+  config: &Configuration,
+) -> Option<usize> {
+  directives
+    .iter()
+    .filter_map(|dir| match dir {
+      Directive::Transaction(txn) => {
+        let txn_text = &content[txn.span.start..txn.span.end];
+        let (_, min_indent) = posting_lines(txn, txn_text, config);
+        widest_posting_prefix(txn, min_indent, config)
+      }
+      _ => None,
+    })
+    .max()
+    .map(|width| width + 3)
+}
+
+/// The indentation and optional flag written before a posting's account. With
+/// `flag_in_margin` the flag moves into the indentation when it fits, so
+/// flagged and unflagged accounts start at the same column.
+fn posting_lead(flag: Option<&str>, indent: usize, config: &Configuration) -> String {
+  match flag {
+    Some(flag) if config.flag_in_margin && indent > flag.len() => {
+      format!("{}{flag} ", " ".repeat(indent - flag.len() - 1))
+    }
+    Some(flag) => format!("{}{flag} ", " ".repeat(indent)),
+    None => " ".repeat(indent),
+  }
+}
+
+/// Aligns an amount so its currency starts at `currency_column`. With
+/// `long_currency = shrink`, an amount that would run past `line_width` moves
+/// left instead, still keeping at least one space after `base`.
+fn align_at_currency_column(
+  base: String,
+  trailing: &str,
+  lead: usize,
+  column: usize,
+  config: &Configuration,
+) -> String {
+  let column = match config.long_currency {
+    LongCurrency::Overflow => column,
+    LongCurrency::Shrink => {
+      let end = column.saturating_sub(1 + lead) + display_width(trailing);
+      column.saturating_sub(end.saturating_sub(config.line_width as usize))
+    }
+  };
+  align_at_column(base, trailing, lead, column)
+}
+
+/// Places `trailing` so that the character `lead` bytes into it starts at the
+/// 1-based `column`. When `base` is too long for that, a single space separates
+/// them instead.
+fn align_at_column(
+  mut base: String,
+  trailing: &str,
+  lead: usize,
+  column: usize,
+) -> String {
+  let desired_start = column.saturating_sub(1).saturating_sub(lead);
+  let base_len = display_width(&base);
+  let start = desired_start.max(base_len + 1);
+  base.push_str(&" ".repeat(start - base_len));
+  base.push_str(trailing);
+  base
+}
+
+/// Places a directive's amount after `base`: its currency starts at
+/// `currency_column` when set, otherwise the amount is right-aligned against
+/// the line width.
+fn place_amount(
+  base: String,
+  amount: &ast::Amount<'_>,
+  config: &Configuration,
+) -> String {
+  let (number, currency) = format_amount_parts(amount, config);
+  let trailing = join_amount(&number, currency.as_deref(), config);
+  match (config.currency_column, currency) {
+    (Some(column), Some(_)) => {
+      let lead = currency_lead(&number, config);
+      align_at_currency_column(base, &trailing, lead, column as usize, config)
+    }
+    _ => align_trailing(base, Some(trailing), config.line_width as usize),
+  }
+}
+
+/// Joins an amount's number and optional currency, separated by
+/// `number_currency_spacing` spaces.
+///
+/// `CurrencyPosition::Before` puts the currency first for display exports. That
+/// order is not valid beancount, so such output won't parse (or format) again.
+fn join_amount(number: &str, currency: Option<&str>, config: &Configuration) -> String {
+  let Some(currency) = currency else {
+    return number.to_string();
+  };
+  let currency = format_currency(currency, config);
+  let spacing = " ".repeat(config.number_currency_spacing as usize);
+  match config.currency_position {
+    CurrencyPosition::After => format!("{number}{spacing}{currency}"),
+    CurrencyPosition::Before => format!("{currency}{spacing}{number}"),
+  }
+}
+
+/// Rewrites an amount given only as text, like a price annotation, the way
+/// [`join_amount`] writes parsed ones. Text that isn't a number with at most
+/// one currency is only whitespace-compacted.
+fn normalize_amount_text(text: &str, config: &Configuration) -> String {
+  let text = compact_ws(text);
+  let lowercase_ok = config.normalize_currency_case;
+  let is_currency = |token: &str| {
+    token.starts_with(|c: char| {
+      c.is_ascii_uppercase() || (lowercase_ok && c.is_ascii_lowercase())
+    })
+  };
+  let (number, currency) = match text.rsplit_once(' ') {
+    Some((number, currency)) if is_currency(currency) => (number, Some(currency)),
+    _ => match text.split_once(' ') {
+      Some((currency, number)) if is_currency(currency) => (number, Some(currency)),
+      _ if is_currency(&text) => return format_currency(&text, config),
+      _ => (text.as_str(), None),
+    },
+  };
+  if number.split(' ').any(is_currency) {
+    return text;
+  }
+  let number = format_thousands(&normalize_expression(number), config);
+  join_amount(&number, currency, config)
+}
+
+/// Rewrites the `,` thousands separators of every number in `text` as
+/// `thousands_separator` asks.
+pub(crate) fn format_thousands(text: &str, config: &Configuration) -> String {
+  if config.thousands_separator == ThousandsSeparator::Preserve {
+    return text.to_string();
+  }
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+    out.push_str(&rest[..start]);
+    let tail = &rest[start..];
+    let len = tail
+      .char_indices()
+      .find(|&(idx, c)| {
+        let grouping = c == ',' && is_thousands_separator(tail, idx);
+        !(c.is_ascii_digit() || c == '.' || grouping)
+      })
+      .map_or(tail.len(), |(idx, _)| idx);
+    let number = &tail[..len];
+    let (integer, fraction) = number.split_at(number.find('.').unwrap_or(len));
+    let digits: String = integer.chars().filter(char::is_ascii_digit).collect();
+    if config.thousands_separator == ThousandsSeparator::Comma {
+      out.push_str(&group_thousands(&digits));
+    } else {
+      out.push_str(&digits);
+    }
+    out.push_str(fraction);
+    rest = &tail[len..];
+  }
+  out.push_str(rest);
+  out
+}
+
+/// `1234567` as `1,234,567`.
+fn group_thousands(digits: &str) -> String {
+  let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+  for (idx, digit) in digits.chars().enumerate() {
+    if idx > 0 && (digits.len() - idx) % 3 == 0 {
+      grouped.push(',');
+    }
+    grouped.push(digit);
+  }
+  grouped
+}
+
+/// [`normalize_amount_text`] for a cost amount, which may be compound: the
+/// per-unit and total parts around `#` are normalized on their own.
+fn normalize_cost_amount(text: &str, config: &Configuration) -> String {
+  match text.split_once('#') {
+    Some((per_unit, total)) => {
+      let per_unit = normalize_amount_text(per_unit, config);
+      let total = normalize_amount_text(total, config);
+      [per_unit.as_str(), "#", total.as_str()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+    None => normalize_amount_text(text, config),
+  }
+}
+
+/// Bytes of a joined amount that precede its currency.
+fn currency_lead(number: &str, config: &Configuration) -> usize {
+  match config.currency_position {
+    CurrencyPosition::After => number.len() + config.number_currency_spacing as usize,
+    CurrencyPosition::Before => 0,
+  }
+}
+
+/// Formats an amount as its number text and, when present, its currency. With
+/// `num_width` set, the number is right-aligned in a field of that width.
+fn format_amount_parts(
+  amount: &ast::Amount<'_>,
+  config: &Configuration,
+) -> (String, Option<String>) {
+  let number_text = format_thousands(&number_text_from_amount(amount), config);
   if let Some(currency) = &amount.currency {
     let cur = currency.content.trim();
     if !number_text.trim().is_empty() && !cur.is_empty() {
-      return Some(format!("{} {}", number_text, cur));
+      let width = config.num_width.unwrap_or(0) as usize;
+      return (format!("{number_text:>width$}"), Some(cur.to_string()));
     }
   }
 
   if number_text.is_empty() {
-    Some(normalize_sign_spacing(&compact_ws(amount.raw.content)))
+    (
+      normalize_sign_spacing(&compact_ws(amount.raw.content)),
+      None,
+    )
   } else {
-    Some(number_text)
+    (number_text, None)
   }
 }
 
-fn format_currencies(currencies: &[WithSpan<&str>]) -> Option<String> {
+fn format_currencies(
+  currencies: &[WithSpan<&str>],
+  config: &Configuration,
+) -> Option<String> {
   if currencies.is_empty() {
     return None;
   }
   Some(
     currencies
       .iter()
-      .map(|c| c.content.trim())
+      .map(|c| format_currency(c.content, config))
       .collect::<Vec<_>>()
       .join(" "),
   )
 }
 
-fn format_tags_links(tags_links: &Option<Vec<WithSpan<&str>>>) -> Option<String> {
-  tags_links.as_ref().and_then(|tags| {
-    let joined = tags
-      .iter()
-      .map(|tag| tag.content.trim())
-      .filter(|tag| !tag.is_empty())
-      .collect::<Vec<_>>()
-      .join(" ");
-    if joined.is_empty() {
-      None
-    } else {
-      Some(joined)
+/// Writes a currency, uppercased with `normalize_currency_case` when that gives
+/// a valid currency. Anything else is kept as written.
+pub(crate) fn format_currency(currency: &str, config: &Configuration) -> String {
+  let currency = currency.trim();
+  if config.normalize_currency_case {
+    let upper = currency.to_ascii_uppercase();
+    if is_currency_name(&upper) {
+      return upper;
     }
-  })
+  }
+  currency.to_string()
 }
 
-fn format_comment(raw: &WithSpan<&str>) -> String {
-  let trimmed = raw.content.trim();
+/// Whether `text` has the shape of a beancount currency: an uppercase letter,
+/// optionally followed by up to 22 letters, digits or `'._-`, ending in an
+/// uppercase letter or digit.
+fn is_currency_name(text: &str) -> bool {
+  let bytes = text.as_bytes();
+  let (Some(first), Some(last)) = (bytes.first(), bytes.last()) else {
+    return false;
+  };
+  first.is_ascii_uppercase()
+    && (last.is_ascii_uppercase() || last.is_ascii_digit())
+    && bytes.len() <= 24
+    && bytes
+      .iter()
+      .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b"'._-".contains(b))
+}
+
+/// Joins the tags and links of a header. `tag_link_grouping` first moves tags
+/// and links into groups, then `sort_tags_links` sorts within each group and
+/// drops exact repeats; the sorts are stable, so the result only depends on the
+/// input order.
+fn format_tags_links<'s>(
+  tags_links: impl IntoIterator<Item = &'s str>,
+  config: &Configuration,
+) -> Option<String> {
+  let mut items = tags_links
+    .into_iter()
+    .map(str::trim)
+    .filter(|tag| !tag.is_empty())
+    .collect::<Vec<_>>();
+  if config.sort_tags_links {
+    items.sort();
+  }
+  match config.tag_link_grouping {
+    TagLinkGrouping::Source => {}
+    TagLinkGrouping::TagsFirst => items.sort_by_key(|item| item.starts_with('^')),
+    TagLinkGrouping::LinksFirst => items.sort_by_key(|item| item.starts_with('#')),
+  }
+  if config.sort_tags_links {
+    items.dedup();
+  }
+  let joined = items.join(" ");
+  if joined.is_empty() {
+    None
+  } else {
+    Some(joined)
+  }
+}
+
+/// Marks the lines of a transaction after its header that hold nothing but
+/// `#tags` and `^links`. Lines inside multi-line strings never count.
+pub(crate) fn tag_link_lines(lines: &[String]) -> Vec<bool> {
+  let mut in_string = false;
+  lines
+    .iter()
+    .enumerate()
+    .map(|(idx, line)| {
+      let starts_in_string = in_string;
+      in_string ^= line.matches('"').count() % 2 == 1;
+      let mut tokens = line.split_whitespace().peekable();
+      idx > 0
+        && !starts_in_string
+        && tokens.peek().is_some()
+        && tokens.all(|token| token.len() > 1 && token.starts_with(['#', '^']))
+    })
+    .collect()
+}
+
+fn format_comment(raw: &WithSpan<&str>, config: &Configuration) -> String {
+  format_comment_text(raw.content, config)
+}
+
+/// Normalizes the spacing after the `;` of a comment.
+fn format_comment_text(text: &str, config: &Configuration) -> String {
+  let trimmed = if config.trim_comment_trailing {
+    text.trim()
+  } else {
+    text.trim_start().trim_end_matches(['\r', '\n'])
+  };
   let without_semicolon = trimmed.strip_prefix(';').unwrap_or(trimmed).trim_start();
-  if without_semicolon.is_empty() {
+  if without_semicolon.trim_end().is_empty() {
     ";".to_string()
+  } else if config.normalize_comment_metadata && looks_like_key_value(without_semicolon)
+  {
+    format!("; {}", normalize_key_value(without_semicolon))
   } else {
     format!("; {}", without_semicolon)
   }
 }
+
+/// Whether comment text has the `key: value` shape of pseudo-metadata, i.e. it
+/// starts with an identifier-like key immediately followed by a colon.
+fn looks_like_key_value(text: &str) -> bool {
+  let Some((key, _)) = text.split_once(':') else {
+    return false;
+  };
+  let key = key.trim_end();
+  let mut chars = key.chars();
+  chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -1,6 +1,19 @@
 pub mod configuration;
+mod cost;
+mod display;
 mod format;
+mod lint;
 mod parse;
+mod raw;
+mod verify;
 
 pub use beancount_parser::ParseError;
-pub use format::format;
+pub use display::format_for_display;
+pub use format::{
+  FormatError, Formatter, format, format_each, format_range, format_sections,
+  format_with_diagnostics, format_with_raw_formatters,
+};
+pub use lint::{LintOptions, LintWarning, lint};
+pub use parse::{SyntaxError, collect_accounts, include_paths, syntax_errors};
+pub use raw::{RawFormatter, RawFormatters};
+pub use verify::verify_semantics;
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use beancount_parser::ast::{self, Directive};
+
+use crate::format::{directive_span, line_col_at_offset};
+use crate::parse::{parse_source, with_trailing_newline};
+
+/// A problem reported by [`lint`]. Linting never changes the source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+  /// 1-based line of the offending text.
+  pub line: usize,
+  /// 1-based column of the offending text.
+  pub column: usize,
+  pub message: String,
+}
+
+impl Display for LintWarning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}: {}", self.line, self.column, self.message)
+  }
+}
+
+/// Optional checks enabled for [`lint`].
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+  /// Warn about metadata values longer than this many characters.
+  pub max_metadata_value_width: Option<usize>,
+}
+
+/// Checks `source` for problems beancount would reject but the formatter keeps
+/// as written. Warnings are returned in source order.
+pub fn lint(source: &str, options: &LintOptions) -> Vec<LintWarning> {
+  let source = with_trailing_newline(source);
+
+  let directives = parse_source(&source);
+  let mut warnings = Vec::new();
+  for dir in &directives {
+    if let Directive::Transaction(txn) = dir {
+      for keys in transaction_metadata_keys(txn, &source) {
+        check_duplicate_metadata(keys, &source, &mut warnings);
+      }
+    } else {
+      let keys = key_value_keys(directive_key_values(dir), &source);
+      check_duplicate_metadata(keys, &source, &mut warnings);
+    }
+    if let Some(limit) = options.max_metadata_value_width {
+      check_long_metadata(dir, &source, limit, &mut warnings);
+    }
+  }
+  check_tag_stack(&directives, &source, &mut warnings);
+  warnings.sort_by_key(|warning| (warning.line, warning.column));
+  warnings
+}
+
+/// Beancount rejects a directive or posting that sets the same metadata key
+/// twice. `keys` holds every key with the offset it starts at.
+fn check_duplicate_metadata(
+  keys: Vec<(&str, usize)>,
+  source: &str,
+  warnings: &mut Vec<LintWarning>,
+) {
+  let mut seen: HashMap<&str, usize> = HashMap::new();
+  for (key, key_offset) in keys {
+    let (line, column) = line_col_at_offset(source, key_offset);
+    if let Some(first_line) = seen.get(key) {
+      warnings.push(LintWarning {
+        line,
+        column,
+        message: format!(
+          "duplicate metadata key `{key}` (first set on line {first_line})"
+        ),
+      });
+    } else {
+      seen.insert(key, line);
+    }
+  }
+}
+
+fn key_value_keys<'s>(
+  key_values: &[ast::KeyValue<'_>],
+  source: &'s str,
+) -> Vec<(&'s str, usize)> {
+  key_values
+    .iter()
+    .map(|kv| {
+      let text = &source[kv.span.start..kv.span.end];
+      let key_offset = kv.span.start + (text.len() - text.trim_start().len());
+      let key = text.split(':').next().unwrap_or_default().trim();
+      (key, key_offset)
+    })
+    .collect()
+}
+
+/// The metadata keys of a transaction and of each of its postings, found by
+/// scanning the indented `key: value` lines like [`check_long_metadata`]. A
+/// line belongs to the last posting that starts before it, or to the
+/// transaction itself before the first posting.
+fn transaction_metadata_keys<'s>(
+  txn: &ast::Transaction<'_>,
+  source: &'s str,
+) -> Vec<Vec<(&'s str, usize)>> {
+  let mut groups = vec![Vec::new(); txn.postings.len() + 1];
+  let mut line_start = txn.span.start;
+  for (idx, line) in source[txn.span.start..txn.span.end]
+    .split_inclusive('\n')
+    .enumerate()
+  {
+    let offset = line_start;
+    line_start += line.len();
+    if idx == 0 {
+      continue;
+    }
+
+    let trimmed = line.trim_start();
+    let Some((key, _)) = trimmed.split_once(':') else {
+      continue;
+    };
+    if !is_metadata_key(key) {
+      continue;
+    }
+    let owner = txn
+      .postings
+      .iter()
+      .filter(|posting| posting.span.start < offset)
+      .count();
+    groups[owner].push((key, offset + (line.len() - trimmed.len())));
+  }
+  groups
+}
+
+/// Flags metadata values longer than `limit` characters, which usually are
+/// accidentally pasted blobs. Metadata is found by scanning the directive's
+/// indented `key: value` lines, so transaction and posting metadata count too.
+fn check_long_metadata(
+  dir: &Directive<'_>,
+  source: &str,
+  limit: usize,
+  warnings: &mut Vec<LintWarning>,
+) {
+  let span = directive_span(dir);
+  let mut line_start = span.start;
+  for (idx, line) in source[span.start..span.end]
+    .split_inclusive('\n')
+    .enumerate()
+  {
+    let offset = line_start;
+    line_start += line.len();
+    if idx == 0 {
+      continue;
+    }
+
+    let trimmed = line.trim_start();
+    let Some((key, value)) = trimmed.split_once(':') else {
+      continue;
+    };
+    if !is_metadata_key(key) {
+      continue;
+    }
+    let width = value.trim().chars().count();
+    if width > limit {
+      let key_offset = offset + (line.len() - trimmed.len());
+      let (line, column) = line_col_at_offset(source, key_offset);
+      warnings.push(LintWarning {
+        line,
+        column,
+        message: format!(
+          "metadata value of `{key}` is {width} characters long (limit {limit})"
+        ),
+      });
+    }
+  }
+}
+
+/// Metadata keys start with a lowercase letter; this tells them apart from
+/// posting accounts and comments.
+pub(crate) fn is_metadata_key(key: &str) -> bool {
+  key.starts_with(|c: char| c.is_ascii_lowercase())
+    && key
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Every `pushtag` needs a later `poptag` of the same tag and vice versa;
+/// otherwise the tag silently applies to the rest of the file.
+fn check_tag_stack(
+  directives: &[Directive<'_>],
+  source: &str,
+  warnings: &mut Vec<LintWarning>,
+) {
+  let mut pushed: Vec<(&str, usize)> = Vec::new();
+  for dir in directives {
+    match dir {
+      Directive::PushTag(d) => pushed.push((d.tag.content.trim(), d.span.start)),
+      Directive::PopTag(d) => {
+        let tag = d.tag.content.trim();
+        if let Some(pos) = pushed.iter().rposition(|(pushed, _)| *pushed == tag) {
+          pushed.remove(pos);
+        } else {
+          let (line, column) = line_col_at_offset(source, d.span.start);
+          warnings.push(LintWarning {
+            line,
+            column,
+            message: format!("poptag #{tag} without a matching pushtag"),
+          });
+        }
+      }
+      _ => {}
+    }
+  }
+  for (tag, offset) in pushed {
+    let (line, column) = line_col_at_offset(source, offset);
+    warnings.push(LintWarning {
+      line,
+      column,
+      message: format!("pushtag #{tag} is never popped"),
+    });
+  }
+}
+
+/// Transactions are left out: their metadata is found by
+/// [`transaction_metadata_keys`].
+fn directive_key_values<'d, 'a>(dir: &'d Directive<'a>) -> &'d [ast::KeyValue<'a>] {
+  match dir {
+    Directive::Open(d) => &d.key_values,
+    Directive::Close(d) => &d.key_values,
+    Directive::Balance(d) => &d.key_values,
+    Directive::Pad(d) => &d.key_values,
+    Directive::Commodity(d) => &d.key_values,
+    Directive::Price(d) => &d.key_values,
+    Directive::Event(d) => &d.key_values,
+    Directive::Query(d) => &d.key_values,
+    Directive::Note(d) => &d.key_values,
+    Directive::Document(d) => &d.key_values,
+    Directive::Custom(d) => &d.key_values,
+    _ => &[],
+  }
+}
@@ -1,5 +1,177 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use anyhow::Result;
+use beancount_parser::ast::{Directive, WithSpan};
 use beancount_parser::{self as parser};
 
+use crate::format::{directive_date_span, line_col_at_offset};
+
 pub fn parse_source<'a>(source: &'a str) -> Vec<parser::ast::Directive<'a>> {
   parser::parse_lossy(source)
 }
+
+/// `source` ending in a newline, which the parser expects after the last
+/// directive. Parse the result with [`parse_source`].
+pub(crate) fn with_trailing_newline(source: &str) -> Cow<'_, str> {
+  if source.ends_with('\n') {
+    Cow::Borrowed(source)
+  } else {
+    Cow::Owned(format!("{}\n", source))
+  }
+}
+
+/// A part of the source the parser could not read as a directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+  /// 1-based line where the unreadable text starts.
+  pub line: usize,
+  /// 1-based column where the unreadable text starts.
+  pub column: usize,
+  pub message: String,
+}
+
+impl Display for SyntaxError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}: {}", self.line, self.column, self.message)
+  }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Every part of `source` the parser could not read, in source order.
+///
+/// The parser recovers from a syntax error, such as an unknown directive or an
+/// unterminated string, by returning the text up to the next directive as a raw
+/// entry, which [`format`](crate::format) keeps as written. It reports nothing
+/// finer than that, so each error points at the start of its raw entry rather
+/// than at the offending token. Dates that are not on the calendar, like
+/// `2010-13-45`, parse fine but count as unreadable too and are reported at the
+/// date itself.
+pub fn syntax_errors(source: &str) -> Vec<SyntaxError> {
+  let source = with_trailing_newline(source);
+
+  parse_source(&source)
+    .iter()
+    .filter_map(|dir| match dir {
+      Directive::Raw(d) => {
+        let (line, column) = line_col_at_offset(&source, d.span.start);
+        let text = source[d.span.start..d.span.end]
+          .lines()
+          .next()
+          .unwrap_or("");
+        Some(SyntaxError {
+          line,
+          column,
+          message: format!("could not parse `{}`", text.trim()),
+        })
+      }
+      dir => {
+        let date = directive_date_span(dir)?;
+        let text = date.content.trim();
+        if is_calendar_date(text) {
+          return None;
+        }
+        let leading = date.content.len() - date.content.trim_start().len();
+        let (line, column) = line_col_at_offset(&source, date.span.start + leading);
+        Some(SyntaxError {
+          line,
+          column,
+          message: format!("invalid date `{}`", text),
+        })
+      }
+    })
+    .collect()
+}
+
+/// The year, month and day of a `YYYY-MM-DD`, `YYYY/MM/DD` or `YYYY.MM.DD`
+/// date, whether or not it exists. Every check for date-shaped text goes through
+/// here.
+pub(crate) fn date_parts(text: &str) -> Option<(&str, &str, &str)> {
+  let bytes = text.as_bytes();
+  let digits =
+    |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+  let well_formed = bytes.len() == 10
+    && matches!(bytes[4], b'-' | b'/' | b'.')
+    && bytes[7] == bytes[4]
+    && digits(0..4)
+    && digits(5..7)
+    && digits(8..10);
+  well_formed.then(|| (&text[0..4], &text[5..7], &text[8..10]))
+}
+
+/// Whether `text` is a [`date_parts`] date that exists.
+fn is_calendar_date(text: &str) -> bool {
+  let Some((year, month, day)) = date_parts(text) else {
+    return false;
+  };
+  let (Ok(year), Ok(month), Ok(day)) = (
+    year.parse::<u32>(),
+    month.parse::<u32>(),
+    day.parse::<u32>(),
+  ) else {
+    return false;
+  };
+  let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+  let days_in_month = match month {
+    1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+    4 | 6 | 9 | 11 => 30,
+    2 if leap => 29,
+    2 => 28,
+    _ => return false,
+  };
+  (1..=days_in_month).contains(&day)
+}
+
+/// Paths named by the `include` directives of `source`, in source order and
+/// without their surrounding quotes.
+pub fn include_paths(source: &str) -> Vec<String> {
+  parse_source(source)
+    .iter()
+    .filter_map(|dir| match dir {
+      parser::ast::Directive::Include(d) => {
+        Some(d.filename.content.trim().trim_matches('"').to_string())
+      }
+      _ => None,
+    })
+    .collect()
+}
+
+/// Every account referenced by the directives and postings of `source`, e.g.
+/// for autocompletion. Fails when part of the file can't be parsed, naming
+/// `path` and the offending line, since the set would be incomplete.
+pub fn collect_accounts(path: &Path, source: &str) -> Result<BTreeSet<String>> {
+  let source = with_trailing_newline(source);
+
+  let mut accounts = BTreeSet::new();
+  for dir in parse_source(&source) {
+    if let Directive::Raw(d) = &dir {
+      let (line, _) = line_col_at_offset(&source, d.span.start);
+      anyhow::bail!("{}:{}: could not parse directive", path.display(), line);
+    }
+    accounts.extend(
+      directive_accounts(&dir)
+        .into_iter()
+        .map(|a| a.content.trim().to_string()),
+    );
+  }
+  Ok(accounts)
+}
+
+/// The accounts a directive and its postings reference, in source order.
+pub(crate) fn directive_accounts<'d, 'a>(
+  dir: &'d Directive<'a>,
+) -> Vec<&'d WithSpan<&'a str>> {
+  match dir {
+    Directive::Open(d) => vec![&d.account],
+    Directive::Close(d) => vec![&d.account],
+    Directive::Balance(d) => vec![&d.account],
+    Directive::Pad(d) => vec![&d.account, &d.from_account],
+    Directive::Note(d) => vec![&d.account],
+    Directive::Document(d) => vec![&d.account],
+    Directive::Transaction(d) => d.postings.iter().map(|p| &p.account).collect(),
+    _ => Vec::new(),
+  }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Formats the text of one raw entry; the result replaces it in the output.
+pub type RawFormatter = dyn Fn(&str) -> String + Send + Sync;
+
+/// Formatters for entries the parser only returns as raw text, such as
+/// directive types it doesn't know. Entries are matched by kind: the word after
+/// the date (`budget` for `2024-01-01 budget ...`), or the first word of an
+/// undated entry. Raw entries without a formatter are kept as written.
+#[derive(Default)]
+pub struct RawFormatters {
+  formatters: HashMap<String, Box<RawFormatter>>,
+}
+
+impl RawFormatters {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Use `formatter` for raw entries of `kind`, replacing any earlier one.
+  pub fn register_raw_formatter(
+    &mut self,
+    kind: impl Into<String>,
+    formatter: impl Fn(&str) -> String + Send + Sync + 'static,
+  ) -> &mut Self {
+    self.formatters.insert(kind.into(), Box::new(formatter));
+    self
+  }
+
+  /// The registered formatter's output for `text`, if its kind has one.
+  pub(crate) fn format(&self, text: &str) -> Option<String> {
+    let formatter = self.formatters.get(raw_kind(text)?)?;
+    Some(formatter(text))
+  }
+}
+
+impl fmt::Debug for RawFormatters {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_set().entries(self.formatters.keys()).finish()
+  }
+}
+
+fn raw_kind(text: &str) -> Option<&str> {
+  let mut words = text.split_whitespace();
+  let first = words.next()?;
+  if first.starts_with(|c: char| c.is_ascii_digit()) {
+    words.next()
+  } else {
+    Some(first)
+  }
+}
@@ -0,0 +1,488 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use anyhow::Result;
+use beancount_parser::ast::{self, Directive, PriceOperator, WithSpan};
+
+use crate::configuration::Configuration;
+use crate::cost::CostSpec;
+use crate::format::{
+  collapse_spaces, compact_ws, format_currency, format_thousands, normalize_date,
+  normalize_include_path, normalize_value_date, tag_link_lines,
+};
+use crate::lint::is_metadata_key;
+use crate::parse::{parse_source, with_trailing_newline};
+
+/// Re-parses `formatted` and checks that its directives mean the same as those
+/// of `original`. Directives are compared by their parsed values, leaving out
+/// spans, layout and comments; with `sort_by_date` or `sort_directives` their
+/// order is ignored as well.
+pub fn verify_semantics(
+  original: &str,
+  formatted: &str,
+  config: &Configuration,
+) -> Result<()> {
+  let mut before = directive_values(original, config);
+  let mut after = directive_values(formatted, config);
+  if config.sort_by_date || config.sort_directives {
+    before.sort();
+    after.sort();
+  }
+
+  for (idx, (before, after)) in before.iter().zip(&after).enumerate() {
+    if before != after {
+      anyhow::bail!(
+        "formatting changed directive {}: `{}` became `{}`",
+        idx + 1,
+        before,
+        after
+      );
+    }
+  }
+  if before.len() != after.len() {
+    anyhow::bail!(
+      "formatting changed the number of directives from {} to {}",
+      before.len(),
+      after.len()
+    );
+  }
+  Ok(())
+}
+
+/// What a directive means, independent of where and how it is written. Only
+/// what the active configuration lets the formatter rewrite is normalized:
+/// dates, thousands separators and currency case are otherwise compared as
+/// written. Tags and links are compared as a set.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct DirectiveValue {
+  date: Option<String>,
+  kind: &'static str,
+  fields: Vec<String>,
+  tags_links: BTreeSet<String>,
+  metadata: Vec<(String, String)>,
+  postings: Vec<PostingValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PostingValue {
+  flag: Option<String>,
+  account: String,
+  amount: Option<String>,
+  cost: Option<String>,
+  price: Option<String>,
+  metadata: Vec<(String, String)>,
+}
+
+impl Display for DirectiveValue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut parts: Vec<&str> = self.date.iter().map(String::as_str).collect();
+    parts.push(self.kind);
+    parts.extend(self.fields.iter().map(String::as_str));
+    parts.extend(self.tags_links.iter().map(String::as_str));
+    write!(f, "{}", parts.join(" "))?;
+    for (key, value) in &self.metadata {
+      write!(f, " {key}: {value}")?;
+    }
+    for posting in &self.postings {
+      let parts = [
+        posting.flag.as_deref(),
+        Some(posting.account.as_str()),
+        posting.amount.as_deref(),
+        posting.cost.as_deref(),
+        posting.price.as_deref(),
+      ];
+      let parts: Vec<&str> = parts.into_iter().flatten().collect();
+      write!(f, "; {}", parts.join(" "))?;
+      for (key, value) in &posting.metadata {
+        write!(f, " {key}: {value}")?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// The value of every directive that carries data, in source order.
+fn directive_values(source: &str, config: &Configuration) -> Vec<DirectiveValue> {
+  let source = with_trailing_newline(source);
+
+  parse_source(&source)
+    .iter()
+    .filter_map(|dir| directive_value(dir, &source, config))
+    .collect()
+}
+
+fn directive_value(
+  dir: &Directive<'_>,
+  source: &str,
+  config: &Configuration,
+) -> Option<DirectiveValue> {
+  let (mut value, key_values) = match dir {
+    Directive::Open(d) => {
+      let mut fields = vec![text(&d.account)];
+      fields.extend(
+        d.currencies
+          .iter()
+          .map(|c| currency_value(c.content, config)),
+      );
+      fields.extend(d.opt_booking.as_ref().map(text));
+      (dated("open", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Close(d) => (
+      dated("close", &d.date, vec![text(&d.account)]),
+      &d.key_values[..],
+    ),
+    Directive::Balance(d) => {
+      let fields = vec![text(&d.account), amount_value(&d.amount, config)];
+      (dated("balance", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Pad(d) => {
+      let fields = vec![text(&d.account), text(&d.from_account)];
+      (dated("pad", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Commodity(d) => {
+      let fields = vec![currency_value(d.currency.content, config)];
+      (dated("commodity", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Price(d) => {
+      let fields = vec![
+        currency_value(d.currency.content, config),
+        amount_value(&d.amount, config),
+      ];
+      (dated("price", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Event(d) => {
+      let fields = vec![text(&d.event_type), text(&d.desc)];
+      (dated("event", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Query(d) => {
+      let fields = vec![text(&d.name), text(&d.query)];
+      (dated("query", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Note(d) => {
+      let fields = vec![text(&d.account), text(&d.note)];
+      (dated("note", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Document(d) => {
+      let fields = vec![text(&d.account), text(&d.filename)];
+      let mut value = dated("document", &d.date, fields);
+      value.tags_links =
+        tags_links_value(d.tags_links.iter().flatten().map(|tag| tag.content));
+      (value, &d.key_values[..])
+    }
+    Directive::Custom(d) => {
+      let mut fields = vec![text(&d.name)];
+      fields.extend(
+        d.values
+          .iter()
+          .map(|v| metadata_value(v.raw.content, config)),
+      );
+      (dated("custom", &d.date, fields), &d.key_values[..])
+    }
+    Directive::Option(d) => {
+      return Some(undated("option", vec![text(&d.key), text(&d.value)]));
+    }
+    Directive::Include(d) => {
+      let mut filename = text(&d.filename);
+      if config.normalize_include_paths {
+        filename = normalize_include_path(&filename);
+      }
+      return Some(undated("include", vec![filename]));
+    }
+    Directive::Plugin(d) => {
+      let mut fields = vec![text(&d.name)];
+      fields.extend(d.config.as_ref().map(text));
+      return Some(undated("plugin", fields));
+    }
+    Directive::PushTag(d) => return Some(undated("pushtag", vec![text(&d.tag)])),
+    Directive::PopTag(d) => return Some(undated("poptag", vec![text(&d.tag)])),
+    Directive::PushMeta(d) => {
+      let mut fields = vec![text(&d.key)];
+      // `pushmeta` values are written as they are, dates included.
+      fields.extend(
+        d.value
+          .as_ref()
+          .map(|value| value.content.trim().to_string()),
+      );
+      return Some(undated("pushmeta", fields));
+    }
+    Directive::PopMeta(d) => return Some(undated("popmeta", vec![text(&d.key)])),
+    Directive::Transaction(d) => return Some(transaction_value(d, source, config)),
+    Directive::Raw(d) => {
+      let raw = compact_ws(&source[d.span.start..d.span.end]);
+      return Some(undated("raw", vec![raw]));
+    }
+    Directive::Headline(_) | Directive::Comment(_) => return None,
+  };
+  value.metadata = key_values
+    .iter()
+    .filter_map(|kv| metadata_entry(&source[kv.span.start..kv.span.end], config))
+    .collect();
+  Some(value)
+}
+
+fn dated(
+  kind: &'static str,
+  date: &WithSpan<&str>,
+  fields: Vec<String>,
+) -> DirectiveValue {
+  DirectiveValue {
+    date: Some(date_value(date.content, config)),
+    ..undated(kind, fields)
+  }
+}
+
+fn undated(kind: &'static str, fields: Vec<String>) -> DirectiveValue {
+  DirectiveValue {
+    date: None,
+    kind,
+    fields,
+    tags_links: BTreeSet::new(),
+    metadata: Vec::new(),
+    postings: Vec::new(),
+  }
+}
+
+fn text(text: &WithSpan<&str>) -> String {
+  text.content.trim().to_string()
+}
+
+/// A transaction with its postings, and its metadata split between the header
+/// and the posting each `key: value` line follows. Tags and links on lines of
+/// their own belong to the header like the ones written on it.
+fn transaction_value(
+  txn: &ast::Transaction<'_>,
+  source: &str,
+  config: &Configuration,
+) -> DirectiveValue {
+  let txn_text = &source[txn.span.start..txn.span.end];
+  let lines: Vec<String> = txn_text.lines().map(str::to_string).collect();
+  let string = |text: &WithSpan<&str>| {
+    let text = text.content.trim();
+    if config.collapse_string_spaces {
+      collapse_spaces(text)
+    } else {
+      text.to_string()
+    }
+  };
+
+  let mut fields: Vec<String> = txn.txn.iter().map(text).collect();
+  fields.extend(txn.payee.as_ref().map(string));
+  fields.extend(txn.narration.as_ref().map(string));
+
+  let continued = tag_link_lines(&lines);
+  let mut tags_links =
+    tags_links_value(txn.tags_links.iter().flatten().map(|tag| tag.content));
+  tags_links.extend(
+    lines
+      .iter()
+      .zip(&continued)
+      .filter(|(_, is_tag_line)| **is_tag_line)
+      .flat_map(|(line, _)| line.split_whitespace().map(str::to_string)),
+  );
+
+  let posting_lines: Vec<usize> = txn
+    .postings
+    .iter()
+    .map(|posting| {
+      let offset = posting.span.start.saturating_sub(txn.span.start);
+      txn_text[..offset.min(txn_text.len())].matches('\n').count()
+    })
+    .collect();
+  let mut postings: Vec<PostingValue> = txn
+    .postings
+    .iter()
+    .map(|posting| {
+      let cost = posting
+        .cost_spec
+        .as_ref()
+        .map(|cost| cost_value(cost.raw.content, config));
+      // A price operator means nothing without the price it applies to.
+      let price = posting.price_annotation.as_ref().map(|price| {
+        let operator = match posting.price_operator.as_ref().map(|op| &op.content) {
+          Some(PriceOperator::PerUnit) => "@ ",
+          Some(PriceOperator::Total) => "@@ ",
+          None => "",
+        };
+        format!("{operator}{}", amount_text_value(price.raw.content, config))
+      });
+      PostingValue {
+        flag: posting.opt_flag.as_ref().map(text),
+        account: text(&posting.account),
+        amount: posting
+          .amount
+          .as_ref()
+          .map(|amount| amount_value(amount, config)),
+        cost,
+        price,
+        metadata: Vec::new(),
+      }
+    })
+    .collect();
+
+  let mut metadata = Vec::new();
+  let mut in_string = false;
+  for (idx, line) in lines.iter().enumerate().skip(1) {
+    let starts_in_string = in_string;
+    in_string ^= line.matches('"').count() % 2 == 1;
+    let owner = posting_lines
+      .iter()
+      .rposition(|&posting_line| posting_line <= idx);
+    let entries = match owner {
+      Some(posting) => &mut postings[posting].metadata,
+      None => &mut metadata,
+    };
+    if starts_in_string {
+      // A multi-line string value goes on with this line.
+      if let Some((_, value)) = entries.last_mut() {
+        value.push('\n');
+        value.push_str(line);
+      }
+      continue;
+    }
+    if posting_lines.contains(&idx) || continued[idx] {
+      continue;
+    }
+    entries.extend(metadata_entry(line, config));
+  }
+  if config.sort_postings {
+    postings.sort_by(|a, b| a.account.cmp(&b.account));
+  }
+
+  DirectiveValue {
+    date: Some(date_value(txn.date.content, config)),
+    kind: "transaction",
+    fields,
+    tags_links,
+    metadata,
+    postings,
+  }
+}
+
+/// A cost with its components in canonical order and their amounts
+/// normalized. Lot dates are compared as written, since they are never
+/// rewritten.
+fn cost_value(raw: &str, config: &Configuration) -> String {
+  let Some(mut spec) = CostSpec::parse(raw) else {
+    return compact_ws(raw);
+  };
+  spec.amount = spec.amount.map(|amount| {
+    amount
+      .split('#')
+      .map(|amount| amount_text_value(amount, config))
+      .collect::<Vec<_>>()
+      .join(" # ")
+  });
+  spec.to_string()
+}
+
+/// The key and value of a `key: value` metadata line, if it is one.
+fn metadata_entry(line: &str, config: &Configuration) -> Option<(String, String)> {
+  let body = strip_comment(line.trim());
+  let (key, value) = body.split_once(':')?;
+  let key = key.trim();
+  is_metadata_key(key).then(|| (key.to_string(), metadata_value(value, config)))
+}
+
+/// A metadata or custom value as written, with a date rewritten only when
+/// `normalize_metadata_dates` lets the formatter do so.
+fn metadata_value(value: &str, config: &Configuration) -> String {
+  normalize_value_date(value.trim(), config)
+}
+
+fn tags_links_value<'s>(tags: impl Iterator<Item = &'s str>) -> BTreeSet<String> {
+  tags
+    .map(|tag| tag.trim().to_string())
+    .filter(|tag| !tag.is_empty())
+    .collect()
+}
+
+fn amount_value(amount: &ast::Amount<'_>, config: &Configuration) -> String {
+  let raw = amount.raw.content.trim();
+  let currency = amount
+    .currency
+    .as_ref()
+    .map(|currency| currency.content.trim())
+    .filter(|currency| !currency.is_empty());
+  let number = currency
+    .and_then(|currency| raw.strip_suffix(currency))
+    .unwrap_or(raw);
+  match currency {
+    Some(currency) => format!(
+      "{} {}",
+      number_value(number, config),
+      currency_value(currency, config)
+    ),
+    None => number_value(number, config),
+  }
+}
+
+/// [`amount_value`] for an amount only known as text, like a price annotation.
+fn amount_text_value(text: &str, config: &Configuration) -> String {
+  let text = text.trim();
+  match text.rsplit_once(char::is_whitespace) {
+    Some((number, currency)) if currency.starts_with(|c: char| c.is_alphabetic()) => {
+      format!(
+        "{} {}",
+        number_value(number, config),
+        currency_value(currency, config)
+      )
+    }
+    _ if text.starts_with(|c: char| c.is_alphabetic()) => currency_value(text, config),
+    _ => number_value(text, config),
+  }
+}
+
+/// A number or arithmetic expression without whitespace or unary plus signs,
+/// which the formatter always drops, and with its thousands separators
+/// rewritten only as `thousands_separator` asks.
+fn number_value(number: &str, config: &Configuration) -> String {
+  let number: String = number.chars().filter(|c| !c.is_whitespace()).collect();
+  let mut value = String::with_capacity(number.len());
+  for ch in number.chars() {
+    let unary_plus = ch == '+'
+      && value
+        .chars()
+        .last()
+        .is_none_or(|last| "+-*/(".contains(last));
+    if !unary_plus {
+      value.push(ch);
+    }
+  }
+  format_thousands(&value, config)
+}
+
+/// A currency, uppercased only when `normalize_currency_case` is set.
+fn currency_value(currency: &str, config: &Configuration) -> String {
+  format_currency(currency, config)
+}
+
+/// A directive date written with `date_separator`, like the formatter always
+/// does; anything else as written.
+fn date_value(date: &str, config: &Configuration) -> String {
+  let date = date.trim();
+  normalize_date(date, config).unwrap_or_else(|| date.to_string())
+}
+
+/// `text` up to a `;` comment outside of string literals.
+fn strip_comment(text: &str) -> &str {
+  let mut in_string = false;
+  let mut escape = false;
+  for (idx, ch) in text.char_indices() {
+    if in_string {
+      if escape {
+        escape = false;
+      } else if ch == '\\' {
+        escape = true;
+      } else if ch == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    match ch {
+      '"' => in_string = true,
+      ';' => return text[..idx].trim_end(),
+      _ => {}
+    }
+  }
+  text
+}
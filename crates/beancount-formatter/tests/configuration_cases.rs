@@ -0,0 +1,53 @@
+use beancount_formatter::configuration::Configuration;
+
+fn property_names(config: &Configuration) -> Vec<String> {
+  config
+    .validate()
+    .into_iter()
+    .map(|diagnostic| diagnostic.property_name)
+    .collect()
+}
+
+#[test]
+fn default_configuration_is_valid() {
+  assert_eq!(
+    property_names(&Configuration::default()),
+    Vec::<String>::new()
+  );
+}
+
+#[test]
+fn currency_column_with_account_amount_spacing_is_reported() {
+  let config = Configuration {
+    currency_column: Some(60),
+    account_amount_spacing: Some(2),
+    ..Configuration::default()
+  };
+  assert_eq!(property_names(&config), vec!["currency_column"]);
+}
+
+#[test]
+fn num_width_wider_than_line_width_is_reported() {
+  let config = Configuration {
+    line_width: 40,
+    num_width: Some(50),
+    ..Configuration::default()
+  };
+  assert_eq!(property_names(&config), vec!["num_width"]);
+}
+
+#[test]
+fn zero_indent_width_with_tabs_is_reported() {
+  let config = Configuration {
+    indent_width: 0,
+    use_tabs: true,
+    ..Configuration::default()
+  };
+  assert_eq!(property_names(&config), vec!["indent_width"]);
+
+  let spaces = Configuration {
+    indent_width: 0,
+    ..Configuration::default()
+  };
+  assert_eq!(property_names(&spaces), Vec::<String>::new());
+}
@@ -5,7 +5,10 @@ fn format_and_check_fixtures() {
   use std::path::Path;
 
   use beancount_formatter::configuration::{
-    Configuration, NewLineKind, PartialConfiguration as CorePartialConfiguration,
+    BlankLines, CommentAnchoring, Configuration, CurrencyPosition, DateSeparator,
+    LongCommentPlacement, LongCurrency, NewLineKind,
+    PartialConfiguration as CorePartialConfiguration, SameDateOrder, TagLinkGrouping,
+    ThousandsSeparator,
   };
   use beancount_formatter::format;
   use serde::Deserialize;
@@ -15,8 +18,51 @@ fn format_and_check_fixtures() {
   struct PartialConfiguration {
     line_width: Option<u32>,
     indent_width: Option<u8>,
-    new_line: Option<NewLineKind>,
+    new_line_kind: Option<NewLineKind>,
     compact_balance_spacing: Option<bool>,
+    minimal: Option<bool>,
+    normalize_comment_metadata: Option<bool>,
+    currency_column: Option<u32>,
+    collapse_string_spaces: Option<bool>,
+    align_keyword: Option<bool>,
+    sort_by_date: Option<bool>,
+    same_date_order: Option<SameDateOrder>,
+    trim_comment_trailing: Option<bool>,
+    align_text_directives: Option<bool>,
+    preserve_leading_blank_lines: Option<bool>,
+    num_width: Option<u32>,
+    account_amount_spacing: Option<u32>,
+    long_currency: Option<LongCurrency>,
+    number_currency_spacing: Option<u8>,
+    preserve_posting_blank_lines: Option<bool>,
+    currency_position: Option<CurrencyPosition>,
+    normalize_include_paths: Option<bool>,
+    prefix_width: Option<u32>,
+    verify_semantics: Option<bool>,
+    use_tabs: Option<bool>,
+    global_account_alignment: Option<bool>,
+    respect_column_hints: Option<bool>,
+    metadata_extra_indent: Option<u8>,
+    blank_lines: Option<BlankLines>,
+    alignment_tolerance: Option<u32>,
+    sort_tags_links: Option<bool>,
+    tag_link_grouping: Option<TagLinkGrouping>,
+    date_separator: Option<DateSeparator>,
+    sort_directives: Option<bool>,
+    sort_postings: Option<bool>,
+    max_blank_lines: Option<u32>,
+    blank_lines_around_transactions: Option<bool>,
+    align_custom: Option<bool>,
+    comment_column: Option<u32>,
+    comment_anchoring: Option<CommentAnchoring>,
+    flag_in_margin: Option<bool>,
+    long_comment_placement: Option<LongCommentPlacement>,
+    normalize_metadata_dates: Option<bool>,
+    price_operator_spacing: Option<u8>,
+    minimize_diff: Option<bool>,
+    normalize_currency_case: Option<bool>,
+    thousands_separator: Option<ThousandsSeparator>,
+    trailing_newline: Option<bool>,
   }
 
   impl PartialConfiguration {
@@ -24,13 +70,56 @@ fn format_and_check_fixtures() {
       CorePartialConfiguration {
         line_width: self.line_width,
         indent_width: self.indent_width,
-        new_line: self.new_line,
+        new_line_kind: self.new_line_kind,
         compact_balance_spacing: self.compact_balance_spacing,
+        minimal: self.minimal,
+        normalize_comment_metadata: self.normalize_comment_metadata,
+        currency_column: self.currency_column,
+        collapse_string_spaces: self.collapse_string_spaces,
+        align_keyword: self.align_keyword,
+        sort_by_date: self.sort_by_date,
+        same_date_order: self.same_date_order,
+        trim_comment_trailing: self.trim_comment_trailing,
+        align_text_directives: self.align_text_directives,
+        preserve_leading_blank_lines: self.preserve_leading_blank_lines,
+        num_width: self.num_width,
+        account_amount_spacing: self.account_amount_spacing,
+        long_currency: self.long_currency,
+        number_currency_spacing: self.number_currency_spacing,
+        preserve_posting_blank_lines: self.preserve_posting_blank_lines,
+        currency_position: self.currency_position,
+        normalize_include_paths: self.normalize_include_paths,
+        prefix_width: self.prefix_width,
+        verify_semantics: self.verify_semantics,
+        use_tabs: self.use_tabs,
+        global_account_alignment: self.global_account_alignment,
+        respect_column_hints: self.respect_column_hints,
+        metadata_extra_indent: self.metadata_extra_indent,
+        blank_lines: self.blank_lines,
+        alignment_tolerance: self.alignment_tolerance,
+        sort_tags_links: self.sort_tags_links,
+        tag_link_grouping: self.tag_link_grouping,
+        date_separator: self.date_separator,
+        sort_directives: self.sort_directives,
+        sort_postings: self.sort_postings,
+        max_blank_lines: self.max_blank_lines,
+        blank_lines_around_transactions: self.blank_lines_around_transactions,
+        align_custom: self.align_custom,
+        comment_column: self.comment_column,
+        comment_anchoring: self.comment_anchoring,
+        flag_in_margin: self.flag_in_margin,
+        long_comment_placement: self.long_comment_placement,
+        normalize_metadata_dates: self.normalize_metadata_dates,
+        price_operator_spacing: self.price_operator_spacing,
+        minimize_diff: self.minimize_diff,
+        normalize_currency_case: self.normalize_currency_case,
+        thousands_separator: self.thousands_separator,
+        trailing_newline: self.trailing_newline,
       }
     }
   }
 
-  fn assert_eq_with_diff(expected: &str, actual: &str) {
+  fn assert_eq_with_diff(expected: &str, actual: &str, message: &str) {
     if expected == actual {
       return;
     }
@@ -41,7 +130,7 @@ fn format_and_check_fixtures() {
       .to_string();
 
     eprintln!("{diff}");
-    panic!("text mismatch; see unified diff above");
+    panic!("{message}; see unified diff above");
   }
 
   fn run_case(input_path: &Path) {
@@ -100,7 +189,7 @@ fn format_and_check_fixtures() {
     // Fixtures in git may be checked out with LF endings even when the case
     // config requests CRLF. Convert expected text to the configured newline
     // style before comparing.
-    let expected = match config.new_line {
+    let expected = match config.new_line_kind {
       NewLineKind::LF => expected.replace("\r\n", "\n"),
       NewLineKind::CRLF => {
         let lf = expected.replace("\r\n", "\n");
@@ -108,17 +197,38 @@ fn format_and_check_fixtures() {
       }
     };
 
-    if expected == formatted {
-      return;
+    if expected != formatted {
+      if update_expected {
+        fs::write(&expected_path, &formatted).unwrap_or_else(|e| {
+          panic!("Failed to write expected {}: {e}", expected_path.display())
+        });
+        eprintln!("updated expected fixture {}", expected_path.display());
+      } else {
+        assert_eq_with_diff(&expected, &formatted, "text mismatch");
+      }
     }
 
-    if update_expected {
-      fs::write(&expected_path, &formatted).unwrap_or_else(|e| {
-        panic!("Failed to write expected {}: {e}", expected_path.display())
+    // Formatting the output again must be a no-op; otherwise the fixture
+    // exposes a pass that only stabilizes on a later run.
+    let reformatted = format(&formatted, &config)
+      .unwrap_or_else(|e| panic!("second format() failed for {case_name}: {e:?}"));
+    assert_eq_with_diff(
+      &formatted,
+      &reformatted,
+      &format!("fixture {case_name} is not idempotent"),
+    );
+
+    // The stored expected output is itself formatted text, so feeding it back
+    // in must reproduce it byte for byte.
+    if !update_expected {
+      let reformatted = format(&expected, &config).unwrap_or_else(|e| {
+        panic!("format() of expected output failed for {case_name}: {e:?}")
       });
-      eprintln!("updated expected fixture {}", expected_path.display());
-    } else {
-      assert_eq_with_diff(&expected, &formatted);
+      assert_eq_with_diff(
+        &expected,
+        &reformatted,
+        &format!("expected output of {case_name} is not a fixed point"),
+      );
     }
   }
 
@@ -165,16 +275,676 @@ fn format_empty_file_is_single_line() {
   use beancount_formatter::format;
 
   let config = Configuration {
-    new_line: NewLineKind::LF,
+    new_line_kind: NewLineKind::LF,
     ..Default::default()
   };
   let formatted_lf = format("\n\n\t  ", &config).expect("format failed");
   assert_eq!(formatted_lf, "");
 
   let config = Configuration {
-    new_line: NewLineKind::LF,
+    new_line_kind: NewLineKind::LF,
     ..Default::default()
   };
   let formatted_crlf = format("  \r\n\r\n", &config).expect("format failed");
   assert_eq!(formatted_crlf, "");
 }
+
+#[test]
+fn price_without_currency_is_an_error() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format;
+
+  let config = Configuration::default();
+  let err = format(
+    "2010-01-01 open Assets:Cash\n2010-01-06 price USD 10\n",
+    &config,
+  )
+  .expect_err("currency-less price amount should be rejected");
+  let message = err.to_string();
+  assert!(message.contains("price directive"), "{message}");
+  assert!(message.contains("line 2"), "{message}");
+}
+
+#[test]
+fn format_sections_formats_each_section_and_keeps_markers() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_sections;
+
+  let source = "\
+; ===== file: a.bean =====  \n\
+2010-01-01 open\tAssets:Cash\n\
+\n\
+\n\
+\n\
+; ===== file: b.bean =====\n\
+2010-01-02 close   Assets:Cash\n";
+  let formatted = format_sections(source, &Configuration::default(), "; ===== file:")
+    .expect("format failed");
+  assert_eq!(
+    formatted,
+    "\
+; ===== file: a.bean =====  \n\
+2010-01-01 open Assets:Cash\n\
+; ===== file: b.bean =====\n\
+2010-01-02 close Assets:Cash\n"
+  );
+}
+
+#[test]
+fn currency_position_after_is_the_default() {
+  use beancount_formatter::configuration::{Configuration, CurrencyPosition};
+  use beancount_formatter::format;
+
+  let config = Configuration {
+    line_width: 40,
+    ..Default::default()
+  };
+  assert_eq!(config.currency_position, CurrencyPosition::After);
+  let formatted = format("2010-01-01 balance Assets:Cash 100.00 USD\n", &config)
+    .expect("format failed");
+  assert_eq!(formatted, "2010-01-01 balance Assets:Cash 100.00 USD\n");
+}
+
+#[test]
+fn currency_position_before_is_export_only() {
+  use beancount_formatter::configuration::{Configuration, CurrencyPosition};
+  use beancount_formatter::format;
+
+  let config = Configuration {
+    line_width: 40,
+    currency_position: CurrencyPosition::Before,
+    ..Default::default()
+  };
+  let formatted = format(
+    "2010-01-01 * \"Coffee\"\n  Assets:Cash  -3.50 USD\n  Expenses:Food\n",
+    &config,
+  )
+  .expect("format failed");
+  // Not valid beancount anymore, so this is only checked once, not re-formatted.
+  assert_eq!(
+    formatted,
+    "2010-01-01 * \"Coffee\"\n  Assets:Cash                 USD -3.50\n  Expenses:Food\n"
+  );
+}
+
+#[test]
+fn verify_semantics_accepts_whitespace_only_changes() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format;
+
+  let config = Configuration {
+    line_width: 40,
+    verify_semantics: true,
+    ..Default::default()
+  };
+  let formatted = format(
+    "2010-01-01 * \"Coffee\"  ; morning\n  Assets:Cash   -3.50 USD\n  Expenses:Food\n",
+    &config,
+  )
+  .expect("format failed");
+  assert!(formatted.contains("-3.50 USD"), "{formatted}");
+}
+
+#[test]
+fn verify_semantics_rejects_changed_amounts() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::verify_semantics;
+
+  let err = verify_semantics(
+    "2010-01-01 balance Assets:Cash 100.00 USD\n",
+    "2010-01-01 balance Assets:Cash 10.00 USD\n",
+    &Configuration::default(),
+  )
+  .expect_err("changed amount should be rejected");
+  let message = err.to_string();
+  assert!(message.contains("100.00"), "{message}");
+}
+
+#[test]
+fn verify_semantics_only_normalizes_what_the_config_rewrites() {
+  use beancount_formatter::configuration::{Configuration, ThousandsSeparator};
+  use beancount_formatter::verify_semantics;
+
+  let config = Configuration::default();
+  let cases = [
+    (
+      "currency case",
+      "2010-01-01 balance Assets:Cash 1 usd\n",
+      "2010-01-01 balance Assets:Cash 1 USD\n",
+    ),
+    (
+      "swapped date",
+      "2010-01-02 open Assets:Cash\n",
+      "2010-02-01 open Assets:Cash\n",
+    ),
+    (
+      "grouped number",
+      "2010-01-01 balance Assets:Cash 1,234.00 USD\n",
+      "2010-01-01 balance Assets:Cash 1234.00 USD\n",
+    ),
+    (
+      "metadata date",
+      "2010-01-01 open Assets:Cash\n  since: 2009-12-31\n",
+      "2010-01-01 open Assets:Cash\n  since: 2009/12/31\n",
+    ),
+  ];
+  for (name, original, formatted) in cases {
+    assert!(
+      verify_semantics(original, formatted, &config).is_err(),
+      "{name} should be rejected"
+    );
+  }
+
+  let stripped = Configuration {
+    thousands_separator: ThousandsSeparator::Strip,
+    ..Default::default()
+  };
+  verify_semantics(
+    "2010-01-01 balance Assets:Cash 1,234.00 USD\n",
+    "2010-01-01 balance Assets:Cash 1234.00 USD\n",
+    &stripped,
+  )
+  .expect("stripped separators are allowed");
+}
+
+#[test]
+fn format_sections_runs_verify_semantics() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_sections;
+
+  let config = Configuration {
+    verify_semantics: true,
+    sort_by_date: true,
+    ..Default::default()
+  };
+  let source = "\
+2010-01-02 close  Assets:Cash
+2010-01-01 open  Assets:Cash
+; ===== file: b.bean
+2010-01-03 open  Assets:Bank
+";
+  let formatted =
+    format_sections(source, &config, "; ===== file:").expect("format failed");
+  assert_eq!(
+    formatted,
+    "\
+2010-01-01 open Assets:Cash
+2010-01-02 close Assets:Cash
+; ===== file: b.bean
+2010-01-03 open Assets:Bank
+"
+  );
+}
+
+#[test]
+fn verify_semantics_accepts_the_formatters_own_rewrites() {
+  use beancount_formatter::configuration::{Configuration, DateSeparator};
+  use beancount_formatter::{format, verify_semantics};
+
+  let safe = Configuration {
+    verify_semantics: true,
+    ..Default::default()
+  };
+  let cases = [
+    (
+      "metadata spacing",
+      "2010-01-01 open Assets:Cash\n  key:\"x\"\n",
+      safe.clone(),
+    ),
+    (
+      "cost component order",
+      "2020-01-02 *\n  Assets:Stock  10 AAPL {2020-01-01, 100 USD}\n  Assets:Cash\n",
+      safe.clone(),
+    ),
+    (
+      "date separator",
+      "2010/01/01 open Assets:Cash\n2010.01.02 close Assets:Cash\n",
+      safe.clone(),
+    ),
+    (
+      "expression spacing",
+      "2010-01-01 *\n  Assets:Cash  (10+5) *2 USD\n  Income:Job\n",
+      safe.clone(),
+    ),
+    (
+      "tags on continuation lines",
+      "2010-01-01 * \"Coffee\"\n  #trip ^receipt\n  Assets:Cash  -3 USD\n  Expenses:Food\n",
+      safe.clone(),
+    ),
+    (
+      "sorted postings",
+      "2010-01-01 *\n  Expenses:Food  3 USD\n    note: \"lunch\"\n  Assets:Cash  -3 USD\n",
+      Configuration {
+        sort_postings: true,
+        ..safe.clone()
+      },
+    ),
+    (
+      "sorted tags and links",
+      "2010-01-01 * \"Coffee\" #b ^x #a #b\n  Assets:Cash  -3 USD\n  Expenses:Food\n",
+      Configuration {
+        sort_tags_links: true,
+        ..safe.clone()
+      },
+    ),
+    (
+      "currency case",
+      "2010-01-01 open Assets:Cash usd\n2010-01-02 balance Assets:Cash 1 usd\n",
+      Configuration {
+        normalize_currency_case: true,
+        ..safe.clone()
+      },
+    ),
+    (
+      "metadata dates",
+      "2010-01-01 open Assets:Cash\n  since: 2009-12-31\n",
+      Configuration {
+        date_separator: DateSeparator::Slash,
+        normalize_metadata_dates: true,
+        ..safe.clone()
+      },
+    ),
+  ];
+
+  for (name, source, config) in cases {
+    let formatted = format(source, &config)
+      .unwrap_or_else(|err| panic!("{name}: format failed: {err:#}"));
+    verify_semantics(source, &formatted, &config)
+      .unwrap_or_else(|err| panic!("{name}: {err:#}\n{formatted}"));
+  }
+}
+
+#[test]
+fn raw_entries_are_kept_as_written() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format;
+
+  let source = "\
+2010-01-01 open Assets:Cash\n\
+\n\
+2010-01-02 budget  Expenses:Food\t100 USD  \n";
+  let formatted = format(source, &Configuration::default()).expect("format failed");
+  assert!(
+    formatted.ends_with("\n2010-01-02 budget  Expenses:Food\t100 USD  \n"),
+    "{formatted}"
+  );
+}
+
+#[test]
+fn raw_formatters_format_their_kind() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::{RawFormatters, format_with_raw_formatters};
+
+  let mut raw_formatters = RawFormatters::new();
+  raw_formatters.register_raw_formatter("budget", |text| {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+  });
+
+  let source = "\
+2010-01-01 open Assets:Cash\n\
+\n\
+2010-01-02 budget  Expenses:Food\t100 USD  \n";
+  let formatted =
+    format_with_raw_formatters(source, &Configuration::default(), &raw_formatters)
+      .expect("format failed");
+  assert!(
+    formatted.ends_with("\n2010-01-02 budget Expenses:Food 100 USD\n"),
+    "{formatted}"
+  );
+}
+
+#[test]
+fn use_tabs_keeps_raw_entries_as_written() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format;
+
+  let config = Configuration {
+    use_tabs: true,
+    ..Configuration::default()
+  };
+  let source = "\
+2010-01-01 open Assets:Cash\n\
+\n\
+2010-01-02 budget Expenses:Food\n\
+    limit: 100 USD\n\
+\n\
+2010-01-03 * \"Coffee\"\n\
+  Assets:Cash -3.50 USD\n\
+  Expenses:Food\n";
+  let formatted = format(source, &config).expect("format failed");
+  assert!(
+    formatted.contains("\n2010-01-02 budget Expenses:Food\n    limit: 100 USD\n"),
+    "{formatted}"
+  );
+  assert!(formatted.contains("\n\tExpenses:Food\n"), "{formatted}");
+}
+
+#[test]
+fn sort_directives_implies_sort_by_date() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format;
+
+  let source = "\
+2010-01-02 open Assets:Bank\n\
+include \"other.beancount\"\n\
+2010-01-01 open Assets:Cash\n";
+  let expected = "\
+include \"other.beancount\"\n\
+2010-01-01 open Assets:Cash\n\
+2010-01-02 open Assets:Bank\n";
+
+  for sort_by_date in [false, true] {
+    let config = Configuration {
+      sort_by_date,
+      sort_directives: true,
+      ..Configuration::default()
+    };
+    let formatted = format(source, &config).expect("format failed");
+    assert_eq!(formatted, expected, "sort_by_date: {sort_by_date}");
+  }
+}
+
+#[test]
+fn tag_link_grouping_applies_before_sorting() {
+  use beancount_formatter::configuration::{Configuration, TagLinkGrouping};
+  use beancount_formatter::format;
+
+  let config = Configuration {
+    sort_tags_links: true,
+    tag_link_grouping: TagLinkGrouping::LinksFirst,
+    ..Default::default()
+  };
+  let formatted = format(
+    "2010-01-01 document Assets:Cash \"a.pdf\" #b ^z #a ^y\n",
+    &config,
+  )
+  .expect("format failed");
+  assert_eq!(
+    formatted,
+    "2010-01-01 document Assets:Cash \"a.pdf\" ^y ^z #a #b\n"
+  );
+}
+
+#[test]
+fn canonical_amounts_are_kept_byte_for_byte() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format;
+
+  let config = Configuration {
+    line_width: 40,
+    ..Default::default()
+  };
+  let source = "2010-01-01 balance Assets:Cash 1,000.00 USD\n\n2010-01-02 * \"Coffee\"\n  Assets:Cash                 -3.50 USD\n  Expenses:Food                3.50 USD\n";
+  let formatted = format(source, &config).expect("format failed");
+  assert_eq!(formatted.as_bytes(), source.as_bytes());
+}
+
+#[test]
+fn format_range_expands_a_selection_to_whole_directives() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_range;
+
+  let config = Configuration {
+    line_width: 40,
+    ..Default::default()
+  };
+  let source = "2010-01-01 open Assets:Cash\n\n2010-01-02 * \"Coffee\"\n  Assets:Cash   -3.50 USD\n  Expenses:Food\n\n2010-01-03 close   Assets:Cash\n";
+  let txn_start = source.find("2010-01-02").unwrap();
+  let txn_end = source.find("\n\n2010-01-03").unwrap() + 1;
+  // Select from the middle of the first posting to the middle of the second.
+  let selection = source.find("-3.50").unwrap()..source.find("Food").unwrap();
+
+  let (range, formatted) =
+    format_range(source, selection, &config).expect("format failed");
+  assert_eq!(range, txn_start..txn_end);
+  assert_eq!(
+    formatted,
+    "2010-01-02 * \"Coffee\"\n  Assets:Cash                 -3.50 USD\n  Expenses:Food\n"
+  );
+}
+
+#[test]
+fn format_range_covers_every_directive_the_selection_touches() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_range;
+
+  let config = Configuration::default();
+  let source = "2010-01-01 open Assets:Cash\n\n2010-01-02 * \"Coffee\"\n  Assets:Cash   -3.50 USD\n  Expenses:Food\n\n2010-01-03 close   Assets:Cash\n";
+  // From inside the transaction's last posting into the `close` directive.
+  let selection = source.find("Food").unwrap()..source.find("close").unwrap();
+
+  let (range, formatted) =
+    format_range(source, selection, &config).expect("format failed");
+  assert_eq!(range, source.find("2010-01-02").unwrap()..source.len());
+  assert!(
+    formatted.ends_with("\n\n2010-01-03 close Assets:Cash\n"),
+    "{formatted}"
+  );
+}
+
+#[test]
+fn format_range_outside_directives_is_unchanged() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_range;
+
+  let source = "2010-01-01 open Assets:Cash\n\n\n2010-01-03 close Assets:Cash\n";
+  let config = Configuration::default();
+  let blank = source.find("\n\n").unwrap() + 1;
+  let (range, formatted) =
+    format_range(source, blank..blank + 1, &config).expect("format failed");
+  assert_eq!(range, blank..blank + 1);
+  assert_eq!(formatted, "\n");
+}
+
+#[test]
+fn format_range_rejects_invalid_ranges() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_range;
+
+  let source = "2010-01-01 open Assets:Café\n";
+  let config = Configuration::default();
+  let accent = source.find('é').unwrap();
+  for range in [0..source.len() + 1, accent + 1..accent + 1, 5..2] {
+    format_range(source, range.clone(), &config)
+      .expect_err(&format!("{range:?} should be rejected"));
+  }
+  // A cursor at the very end is still a valid selection.
+  format_range(source, source.len()..source.len(), &config).expect("format failed");
+}
+
+#[test]
+fn format_range_at_the_end_honors_trailing_newline() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_range;
+
+  let config = Configuration {
+    trailing_newline: false,
+    ..Default::default()
+  };
+  let source = "2010-01-01 open Assets:Cash\n2010-01-03 close   Assets:Cash\n";
+  let close = source.find("close").unwrap();
+  let (range, formatted) =
+    format_range(source, close..close, &config).expect("format failed");
+  assert_eq!(range, source.find("2010-01-03").unwrap()..source.len());
+  assert_eq!(formatted, "2010-01-03 close Assets:Cash");
+
+  let (_, formatted) = format_range(source, 0..0, &config).expect("format failed");
+  assert_eq!(formatted, "2010-01-01 open Assets:Cash\n");
+}
+
+#[test]
+fn format_with_diagnostics_reports_every_syntax_error() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::{FormatError, format_with_diagnostics};
+
+  let source = "\
+2010-01-01 open Assets:Cash\n\
+2010-01-02 budget Expenses:Food 100 USD\n\
+\n\
+2010-01-03 open Assets:Bank\n\
+2010-01-04 budget Expenses:Rent 900 USD\n";
+  let Err(FormatError::Syntax(errors)) =
+    format_with_diagnostics(source, &Configuration::default())
+  else {
+    panic!("expected syntax errors");
+  };
+  let positions: Vec<(usize, usize)> =
+    errors.iter().map(|err| (err.line, err.column)).collect();
+  assert_eq!(positions, vec![(2, 1), (5, 1)]);
+  assert!(errors[0].message.contains("budget"), "{}", errors[0]);
+}
+
+#[test]
+fn format_each_fragment_matches_the_formatted_file() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::{format, format_each};
+  use std::path::Path;
+
+  let source = "\
+2010-01-01 open Assets:Cash
+2010-01-01 open Expenses:Food
+2010-01-02 * \"Lunch\"
+  Expenses:Food  12.50 USD
+  Assets:Cash
+2010-01-03 close Assets:Cash
+";
+  let config = Configuration::default();
+  let fragments =
+    format_each(Path::new("ledger.bean"), source, &config).expect("format failed");
+  assert_eq!(fragments.len(), 4);
+
+  let (span, fragment) = &fragments[2];
+  assert!(source[span.clone()].starts_with("2010-01-02 * \"Lunch\""));
+  let formatted = format(source, &config).expect("format failed");
+  let start = formatted.find("2010-01-02").unwrap();
+  let end = formatted.find("2010-01-03").unwrap();
+  assert_eq!(
+    fragment.trim_end_matches('\n'),
+    formatted[start..end].trim_end_matches('\n')
+  );
+  assert!(fragment.ends_with("Assets:Cash\n"), "{fragment:?}");
+}
+
+#[test]
+fn format_for_display_abbreviates_parent_accounts() {
+  use beancount_formatter::configuration::Configuration;
+  use beancount_formatter::format_for_display;
+
+  let source = "\
+2010-01-01 open Assets:Investments:Brokerage:Vanguard
+2010-01-02 * \"Buy\"
+  Assets:Investments:Brokerage:Vanguard  10 USD
+  Assets:Cash
+";
+  let config = Configuration {
+    line_width: 40,
+    ..Configuration::default()
+  };
+  let display = format_for_display(source, &config, true).expect("format failed");
+  assert_eq!(
+    display,
+    "\
+2010-01-01 open A:I:B:Vanguard
+
+2010-01-02 * \"Buy\"
+  A:I:B:Vanguard                 10 USD
+  A:Cash
+"
+  );
+
+  let full = format_for_display(source, &config, false).expect("format failed");
+  assert!(
+    full.contains("Assets:Investments:Brokerage:Vanguard"),
+    "{full}"
+  );
+}
+
+#[test]
+fn trailing_newline_can_be_dropped() {
+  use beancount_formatter::configuration::{Configuration, NewLineKind};
+  use beancount_formatter::format;
+
+  let single = "2010-01-01 open   Assets:Cash\n";
+  let multiple = "2010-01-01 open Assets:Cash\n2010-01-02 close   Assets:Cash\n\n";
+  let kept = Configuration::default();
+  let dropped = Configuration {
+    trailing_newline: false,
+    ..Configuration::default()
+  };
+
+  assert_eq!(
+    format(single, &kept).unwrap(),
+    "2010-01-01 open Assets:Cash\n"
+  );
+  assert_eq!(
+    format(single, &dropped).unwrap(),
+    "2010-01-01 open Assets:Cash"
+  );
+  assert_eq!(
+    format(multiple, &kept).unwrap(),
+    "2010-01-01 open Assets:Cash\n2010-01-02 close Assets:Cash\n"
+  );
+  let formatted = format(multiple, &dropped).unwrap();
+  assert_eq!(
+    formatted,
+    "2010-01-01 open Assets:Cash\n2010-01-02 close Assets:Cash"
+  );
+  assert_eq!(format(&formatted, &dropped).unwrap(), formatted);
+
+  let crlf = Configuration {
+    new_line_kind: NewLineKind::CRLF,
+    ..dropped
+  };
+  assert_eq!(
+    format(multiple, &crlf).unwrap(),
+    "2010-01-01 open Assets:Cash\r\n2010-01-02 close Assets:Cash"
+  );
+}
+
+#[test]
+fn formatter_instance_formats_repeatedly() {
+  use beancount_formatter::Formatter;
+  use beancount_formatter::configuration::Configuration;
+  use std::path::Path;
+
+  let source = "2010-01-01 open\tAssets:Cash   \n2010-01-02 close  Assets:Cash\n";
+  let expected = "2010-01-01 open Assets:Cash\n2010-01-02 close Assets:Cash\n";
+  let config = Configuration::default();
+  let mut formatter = Formatter::new();
+  for _ in 0..1000 {
+    let formatted = formatter
+      .format(Path::new("ledger.bean"), source, &config)
+      .expect("format failed");
+    assert_eq!(formatted, expected);
+  }
+}
+
+#[test]
+fn formatter_instance_formats_many_inputs() {
+  use beancount_formatter::Formatter;
+  use beancount_formatter::configuration::Configuration;
+  use std::path::Path;
+
+  let config = Configuration::default();
+  let mut formatter = Formatter::new();
+  let formatted = formatter
+    .format(
+      Path::new("a.bean"),
+      "2010-01-01 open\tAssets:Cash   \n",
+      &config,
+    )
+    .expect("format failed");
+  assert_eq!(formatted, "2010-01-01 open Assets:Cash\n");
+  let formatted = formatter
+    .format(
+      Path::new("b.bean"),
+      "2010-01-01  close  Assets:Cash\n",
+      &config,
+    )
+    .expect("format failed");
+  assert_eq!(formatted, "2010-01-01 close Assets:Cash\n");
+  let err = formatter
+    .format(Path::new("c.bean"), "2010-01-01 price HOOL 100\n", &config)
+    .unwrap_err();
+  assert!(format!("{err:#}").contains("c.bean"), "{err:#}");
+}
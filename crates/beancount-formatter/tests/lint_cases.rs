@@ -0,0 +1,121 @@
+use beancount_formatter::{LintOptions, LintWarning, lint};
+
+#[test]
+fn reports_duplicate_metadata_key() {
+  let source = "\
+2010-01-01 open Assets:Cash
+  name: \"Wallet\"
+  bank: \"None\"
+  name: \"Pocket\"
+";
+  let warnings = lint(source, &LintOptions::default());
+  assert_eq!(
+    warnings,
+    vec![LintWarning {
+      line: 4,
+      column: 3,
+      message: "duplicate metadata key `name` (first set on line 2)".to_string(),
+    }]
+  );
+}
+
+#[test]
+fn reports_duplicate_transaction_metadata_key() {
+  let source = "\
+2010-01-01 * \"Lunch\"
+  receipt: \"r-1\"
+  receipt: \"r-2\"
+  Expenses:Food  10 USD
+  Assets:Cash
+";
+  let warnings = lint(source, &LintOptions::default());
+  assert_eq!(
+    warnings,
+    vec![LintWarning {
+      line: 3,
+      column: 3,
+      message: "duplicate metadata key `receipt` (first set on line 2)".to_string(),
+    }]
+  );
+}
+
+#[test]
+fn reports_duplicate_posting_metadata_key() {
+  let source = "\
+2010-01-01 * \"Lunch\"
+  note: \"header\"
+  Expenses:Food  10 USD
+    note: \"food\"
+  Assets:Cash
+    note: \"cash\"
+    note: \"again\"
+";
+  let warnings = lint(source, &LintOptions::default());
+  assert_eq!(
+    warnings,
+    vec![LintWarning {
+      line: 7,
+      column: 5,
+      message: "duplicate metadata key `note` (first set on line 6)".to_string(),
+    }]
+  );
+}
+
+#[test]
+fn distinct_metadata_keys_are_clean() {
+  let source = "2010-01-01 open Assets:Cash\n  name: \"Wallet\"\n  bank: \"None\"\n";
+  assert!(lint(source, &LintOptions::default()).is_empty());
+}
+
+#[test]
+fn reports_unmatched_pushtag() {
+  let source = "\
+pushtag #trip
+pushtag #work
+2010-01-01 open Assets:Cash
+poptag #work
+";
+  let warnings = lint(source, &LintOptions::default());
+  assert_eq!(
+    warnings,
+    vec![LintWarning {
+      line: 1,
+      column: 1,
+      message: "pushtag #trip is never popped".to_string(),
+    }]
+  );
+}
+
+#[test]
+fn reports_poptag_without_pushtag() {
+  let warnings = lint("poptag #trip\n", &LintOptions::default());
+  assert_eq!(warnings.len(), 1);
+  assert_eq!((warnings[0].line, warnings[0].column), (1, 1));
+  assert_eq!(
+    warnings[0].message,
+    "poptag #trip without a matching pushtag"
+  );
+}
+
+#[test]
+fn reports_overlong_metadata_value() {
+  let source = "\
+2010-01-01 * \"Paste\"
+  blob: \"0123456789abcdef\"
+  ok: \"short\"
+  Assets:Cash  -1 USD
+  Expenses:Food
+";
+  let options = LintOptions {
+    max_metadata_value_width: Some(10),
+  };
+  assert_eq!(
+    lint(source, &options),
+    vec![LintWarning {
+      line: 2,
+      column: 3,
+      message: "metadata value of `blob` is 18 characters long (limit 10)".to_string(),
+    }]
+  );
+  assert!(lint(source, &LintOptions::default()).is_empty());
+}
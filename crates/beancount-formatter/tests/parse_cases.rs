@@ -0,0 +1,57 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use beancount_formatter::{collect_accounts, syntax_errors};
+
+#[test]
+fn collects_directive_and_posting_accounts() {
+  let source = "\
+option \"title\" \"Accounts\"
+2010-01-01 open Assets:Cash USD
+2010-01-01 open Equity:Opening
+2010-01-02 pad Assets:Cash Equity:Opening
+2010-01-03 balance Assets:Cash 100 USD
+2010-01-04 note Liabilities:Card \"Ordered a card\"
+2010-01-05 * \"Coffee\"
+  Assets:Cash  -3 USD
+  Expenses:Food:Coffee
+2010-12-31 close Assets:Cash
+";
+  let accounts =
+    collect_accounts(Path::new("ledger.bean"), source).expect("collect failed");
+  let expected: BTreeSet<String> = [
+    "Assets:Cash",
+    "Equity:Opening",
+    "Expenses:Food:Coffee",
+    "Liabilities:Card",
+  ]
+  .into_iter()
+  .map(String::from)
+  .collect();
+  assert_eq!(accounts, expected);
+}
+
+#[test]
+fn reports_an_unterminated_string() {
+  let source = "\
+2010-01-01 open Assets:Cash
+2010-01-02 note Assets:Cash \"never closed
+";
+  let errors = syntax_errors(source);
+  let lines: Vec<usize> = errors.iter().map(|err| err.line).collect();
+  assert_eq!(lines, vec![2], "{errors:?}");
+}
+
+#[test]
+fn reports_a_malformed_date_at_the_date() {
+  let source = "\
+2010-01-01 open Assets:Cash
+2010-13-45 open Assets:Bank
+2012-02-29 open Assets:Card
+2012.03.01 open Assets:Wallet
+";
+  let errors = syntax_errors(source);
+  assert_eq!(errors.len(), 1, "{errors:?}");
+  assert_eq!((errors[0].line, errors[0].column), (2, 1));
+  assert!(errors[0].message.contains("2010-13-45"), "{}", errors[0]);
+}
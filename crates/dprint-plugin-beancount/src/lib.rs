@@ -3,6 +3,7 @@
 use beancount_formatter::configuration::Configuration;
 use beancount_formatter::configuration::NewLineKind;
 use beancount_formatter::format as format_beancount;
+use beancount_formatter::format_range as format_beancount_range;
 use dprint_core::configuration::ConfigKeyMap;
 use dprint_core::configuration::GlobalConfiguration;
 use dprint_core::configuration::NewLineKind as DprintNewLineKind;
@@ -80,8 +81,11 @@ impl SyncPluginHandler<Configuration> for BeancountPluginHandler {
     if file_text.trim().is_empty() {
       return Ok(Some(Vec::new()));
     }
-    let formatted = format_beancount(&file_text, request.config)
-      .map_err(dprint_core::plugins::FormatError::new)?;
+    let formatted = match request.range {
+      Some(range) => format_selection(&file_text, range, request.config)?,
+      None => format_beancount(&file_text, request.config)
+        .map_err(dprint_core::plugins::FormatError::new)?,
+    };
 
     if formatted == file_text {
       Ok(None)
@@ -91,11 +95,29 @@ impl SyncPluginHandler<Configuration> for BeancountPluginHandler {
   }
 }
 
+/// Formats only the directives intersecting `range` and splices them back into
+/// `file_text`, leaving everything outside them byte-identical.
+fn format_selection(
+  file_text: &str,
+  range: std::ops::Range<usize>,
+  config: &Configuration,
+) -> Result<String, dprint_core::plugins::FormatError> {
+  let (range, replacement) = format_beancount_range(file_text, range, config)
+    .map_err(dprint_core::plugins::FormatError::new)?;
+  let mut formatted = String::with_capacity(file_text.len());
+  formatted.push_str(&file_text[..range.start]);
+  formatted.push_str(&replacement);
+  formatted.push_str(&file_text[range.end..]);
+  Ok(formatted)
+}
+
 fn resolve_config_dprint(
   config: ConfigKeyMap,
   global_config: &GlobalConfiguration,
 ) -> dprint_core::configuration::ResolveConfigurationResult<Configuration> {
+  use dprint_core::configuration::ConfigurationDiagnostic;
   use dprint_core::configuration::ResolveConfigurationResult;
+  use dprint_core::configuration::get_nullable_value;
   use dprint_core::configuration::get_unknown_property_diagnostics;
   use dprint_core::configuration::get_value;
 
@@ -107,7 +129,7 @@ fn resolve_config_dprint(
   let global_line_width = global_config.line_width.unwrap_or(default.line_width);
   let global_indent_width = global_config.indent_width.unwrap_or(default.indent_width);
   let global_new_line = map_new_line_kind(global_config.new_line_kind.unwrap_or(
-    match default.new_line {
+    match default.new_line_kind {
       NewLineKind::LF => DprintNewLineKind::LineFeed,
       NewLineKind::CRLF => DprintNewLineKind::CarriageReturnLineFeed,
     },
@@ -126,7 +148,7 @@ fn resolve_config_dprint(
       global_indent_width,
       &mut diagnostics,
     ),
-    new_line: map_new_line_kind(get_value(
+    new_line_kind: map_new_line_kind(get_value(
       &mut config,
       "new_line",
       match global_new_line {
@@ -141,9 +163,251 @@ fn resolve_config_dprint(
       default.compact_balance_spacing,
       &mut diagnostics,
     ),
+    minimal: get_value(&mut config, "minimal", default.minimal, &mut diagnostics),
+    normalize_comment_metadata: get_value(
+      &mut config,
+      "normalize_comment_metadata",
+      default.normalize_comment_metadata,
+      &mut diagnostics,
+    ),
+    currency_column: get_nullable_value(
+      &mut config,
+      "currency_column",
+      &mut diagnostics,
+    ),
+    collapse_string_spaces: get_value(
+      &mut config,
+      "collapse_string_spaces",
+      default.collapse_string_spaces,
+      &mut diagnostics,
+    ),
+    align_keyword: get_value(
+      &mut config,
+      "align_keyword",
+      default.align_keyword,
+      &mut diagnostics,
+    ),
+    sort_by_date: get_value(
+      &mut config,
+      "sort_by_date",
+      default.sort_by_date,
+      &mut diagnostics,
+    ),
+    same_date_order: get_value(
+      &mut config,
+      "same_date_order",
+      default.same_date_order,
+      &mut diagnostics,
+    ),
+    trim_comment_trailing: get_value(
+      &mut config,
+      "trim_comment_trailing",
+      default.trim_comment_trailing,
+      &mut diagnostics,
+    ),
+    align_text_directives: get_value(
+      &mut config,
+      "align_text_directives",
+      default.align_text_directives,
+      &mut diagnostics,
+    ),
+    preserve_leading_blank_lines: get_value(
+      &mut config,
+      "preserve_leading_blank_lines",
+      default.preserve_leading_blank_lines,
+      &mut diagnostics,
+    ),
+    num_width: get_nullable_value(&mut config, "num_width", &mut diagnostics),
+    account_amount_spacing: get_nullable_value(
+      &mut config,
+      "account_amount_spacing",
+      &mut diagnostics,
+    ),
+    long_currency: get_value(
+      &mut config,
+      "long_currency",
+      default.long_currency,
+      &mut diagnostics,
+    ),
+    number_currency_spacing: get_value(
+      &mut config,
+      "number_currency_spacing",
+      default.number_currency_spacing,
+      &mut diagnostics,
+    ),
+    preserve_posting_blank_lines: get_value(
+      &mut config,
+      "preserve_posting_blank_lines",
+      default.preserve_posting_blank_lines,
+      &mut diagnostics,
+    ),
+    currency_position: get_value(
+      &mut config,
+      "currency_position",
+      default.currency_position,
+      &mut diagnostics,
+    ),
+    normalize_include_paths: get_value(
+      &mut config,
+      "normalize_include_paths",
+      default.normalize_include_paths,
+      &mut diagnostics,
+    ),
+    prefix_width: get_nullable_value(&mut config, "prefix_width", &mut diagnostics),
+    verify_semantics: get_value(
+      &mut config,
+      "verify_semantics",
+      default.verify_semantics,
+      &mut diagnostics,
+    ),
+    use_tabs: get_value(
+      &mut config,
+      "use_tabs",
+      global_config.use_tabs.unwrap_or(default.use_tabs),
+      &mut diagnostics,
+    ),
+    global_account_alignment: get_value(
+      &mut config,
+      "global_account_alignment",
+      default.global_account_alignment,
+      &mut diagnostics,
+    ),
+    respect_column_hints: get_value(
+      &mut config,
+      "respect_column_hints",
+      default.respect_column_hints,
+      &mut diagnostics,
+    ),
+    metadata_extra_indent: get_value(
+      &mut config,
+      "metadata_extra_indent",
+      default.metadata_extra_indent,
+      &mut diagnostics,
+    ),
+    blank_lines: get_value(
+      &mut config,
+      "blank_lines",
+      default.blank_lines,
+      &mut diagnostics,
+    ),
+    alignment_tolerance: get_value(
+      &mut config,
+      "alignment_tolerance",
+      default.alignment_tolerance,
+      &mut diagnostics,
+    ),
+    sort_tags_links: get_value(
+      &mut config,
+      "sort_tags_links",
+      default.sort_tags_links,
+      &mut diagnostics,
+    ),
+    tag_link_grouping: get_value(
+      &mut config,
+      "tag_link_grouping",
+      default.tag_link_grouping,
+      &mut diagnostics,
+    ),
+    date_separator: get_value(
+      &mut config,
+      "date_separator",
+      default.date_separator,
+      &mut diagnostics,
+    ),
+    sort_directives: get_value(
+      &mut config,
+      "sort_directives",
+      default.sort_directives,
+      &mut diagnostics,
+    ),
+    sort_postings: get_value(
+      &mut config,
+      "sort_postings",
+      default.sort_postings,
+      &mut diagnostics,
+    ),
+    max_blank_lines: get_value(
+      &mut config,
+      "max_blank_lines",
+      default.max_blank_lines,
+      &mut diagnostics,
+    ),
+    blank_lines_around_transactions: get_value(
+      &mut config,
+      "blank_lines_around_transactions",
+      default.blank_lines_around_transactions,
+      &mut diagnostics,
+    ),
+    align_custom: get_value(
+      &mut config,
+      "align_custom",
+      default.align_custom,
+      &mut diagnostics,
+    ),
+    comment_column: get_nullable_value(&mut config, "comment_column", &mut diagnostics),
+    comment_anchoring: get_value(
+      &mut config,
+      "comment_anchoring",
+      default.comment_anchoring,
+      &mut diagnostics,
+    ),
+    flag_in_margin: get_value(
+      &mut config,
+      "flag_in_margin",
+      default.flag_in_margin,
+      &mut diagnostics,
+    ),
+    long_comment_placement: get_value(
+      &mut config,
+      "long_comment_placement",
+      default.long_comment_placement,
+      &mut diagnostics,
+    ),
+    normalize_metadata_dates: get_value(
+      &mut config,
+      "normalize_metadata_dates",
+      default.normalize_metadata_dates,
+      &mut diagnostics,
+    ),
+    price_operator_spacing: get_value(
+      &mut config,
+      "price_operator_spacing",
+      default.price_operator_spacing,
+      &mut diagnostics,
+    ),
+    minimize_diff: get_value(
+      &mut config,
+      "minimize_diff",
+      default.minimize_diff,
+      &mut diagnostics,
+    ),
+    normalize_currency_case: get_value(
+      &mut config,
+      "normalize_currency_case",
+      default.normalize_currency_case,
+      &mut diagnostics,
+    ),
+    thousands_separator: get_value(
+      &mut config,
+      "thousands_separator",
+      default.thousands_separator,
+      &mut diagnostics,
+    ),
+    trailing_newline: get_value(
+      &mut config,
+      "trailing_newline",
+      default.trailing_newline,
+      &mut diagnostics,
+    ),
   };
 
   diagnostics.extend(get_unknown_property_diagnostics(config));
+  diagnostics.extend(resolved_config.validate().into_iter().map(|diagnostic| {
+    ConfigurationDiagnostic {
+      property_name: diagnostic.property_name,
+      message: diagnostic.message,
+    }
+  }));
 
   ResolveConfigurationResult {
     config: resolved_config,
@@ -158,3 +422,72 @@ fn map_new_line_kind(value: DprintNewLineKind) -> NewLineKind {
     _ => NewLineKind::LF,
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use dprint_core::configuration::ConfigKeyValue;
+
+  fn resolve(entries: &[(&str, ConfigKeyValue)]) -> (Configuration, Vec<String>) {
+    let config: ConfigKeyMap = entries
+      .iter()
+      .map(|(key, value)| (key.to_string(), value.clone()))
+      .collect();
+    let result = resolve_config_dprint(config, &GlobalConfiguration::default());
+    let diagnostics = result
+      .diagnostics
+      .iter()
+      .map(|diagnostic| diagnostic.property_name.clone())
+      .collect();
+    (result.config, diagnostics)
+  }
+
+  #[test]
+  fn formats_only_the_selected_transaction() {
+    let text = "2010-01-01   open Assets:Cash\n\n\
+                2010-01-02 *   \"Coffee\"\n  Assets:Cash  -3 USD\n  Expenses:Food\n\n\
+                2010-01-03   close Assets:Cash\n";
+    let start = text.find("2010-01-02").unwrap();
+    let formatted =
+      format_selection(text, start..start + 10, &Configuration::default()).unwrap();
+
+    assert!(formatted.starts_with("2010-01-01   open Assets:Cash\n\n"));
+    assert!(formatted.ends_with("\n2010-01-03   close Assets:Cash\n"));
+    assert!(formatted.contains("2010-01-02 * \"Coffee\"\n"));
+  }
+
+  #[test]
+  fn resolves_width_and_spacing_keys() {
+    let (config, diagnostics) = resolve(&[
+      ("line_width", ConfigKeyValue::from_i32(100)),
+      ("indent_width", ConfigKeyValue::from_i32(4)),
+      ("new_line", ConfigKeyValue::from_str("crlf")),
+      ("prefix_width", ConfigKeyValue::from_i32(40)),
+      ("num_width", ConfigKeyValue::from_i32(12)),
+      ("currency_column", ConfigKeyValue::from_i32(70)),
+      ("number_currency_spacing", ConfigKeyValue::from_i32(2)),
+    ]);
+
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    assert_eq!(config.line_width, 100);
+    assert_eq!(config.indent_width, 4);
+    assert_eq!(config.new_line_kind, NewLineKind::CRLF);
+    assert_eq!(config.prefix_width, Some(40));
+    assert_eq!(config.num_width, Some(12));
+    assert_eq!(config.currency_column, Some(70));
+    assert_eq!(config.account_amount_spacing, None);
+    assert_eq!(config.number_currency_spacing, 2);
+  }
+
+  #[test]
+  fn reports_invalid_and_conflicting_values() {
+    let (_, diagnostics) = resolve(&[
+      ("num_width", ConfigKeyValue::from_str("wide")),
+      ("currency_column", ConfigKeyValue::from_i32(70)),
+      ("account_amount_spacing", ConfigKeyValue::from_i32(2)),
+    ]);
+
+    assert!(diagnostics.contains(&"num_width".to_string()));
+    assert!(diagnostics.contains(&"currency_column".to_string()));
+  }
+}
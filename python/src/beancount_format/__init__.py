@@ -1,13 +1,48 @@
 """Python bindings for the beancount formatter."""
 
+import os
 import sys
+from typing import Any
 
 from .beancount_format import (
     format_text,
     main as _main,
 )
 
-__all__ = ["format_text", "main"]
+__all__ = ["check_text", "format_file", "format_text", "main"]
+
+
+def check_text(text: str, **config: Any) -> bool:
+    """Whether `text` is already formatted with the options of `format_text`.
+
+    `format_text` returns its input unchanged when there is nothing to do, so a
+    `--check`-style hook can call this instead of comparing the output itself.
+    """
+
+    return format_text(text, **config) == text
+
+
+def format_file(
+    path: str | os.PathLike[str], *, in_place: bool = False, **config: Any
+) -> str | bool:
+    """Format the file at `path` with the options of `format_text`.
+
+    Returns the formatted text, or with `in_place=True` writes it back and
+    returns whether the file changed. Line endings are kept as read, so
+    `new_line` decides them. Raises `FileNotFoundError` or another `OSError`
+    when the file can't be read or written.
+    """
+
+    with open(path, encoding="utf-8", newline="") as file:
+        source = file.read()
+    formatted = format_text(source, **config)
+    if not in_place:
+        return formatted
+    if formatted == source:
+        return False
+    with open(path, "w", encoding="utf-8", newline="") as file:
+        file.write(formatted)
+    return True
 
 
 def main(argv: list[str] | None = None) -> None:
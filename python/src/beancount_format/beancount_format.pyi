@@ -1,3 +1,4 @@
+import os
 from collections.abc import Sequence
 
 __all__ = ["format_text", "main"]
@@ -5,9 +6,53 @@ __all__ = ["format_text", "main"]
 def format_text(
     text: str,
     *,
+    filename: str | os.PathLike[str] | None = ...,
     line_width: int | None = ...,
     indent_width: int | None = ...,
     new_line: str | None = ...,
     compact_balance_spacing: bool | None = ...,
+    minimal: bool | None = ...,
+    normalize_comment_metadata: bool | None = ...,
+    currency_column: int | None = ...,
+    collapse_string_spaces: bool | None = ...,
+    align_keyword: bool | None = ...,
+    sort_by_date: bool | None = ...,
+    same_date_order: str | None = ...,
+    trim_comment_trailing: bool | None = ...,
+    align_text_directives: bool | None = ...,
+    preserve_leading_blank_lines: bool | None = ...,
+    num_width: int | None = ...,
+    account_amount_spacing: int | None = ...,
+    long_currency: str | None = ...,
+    number_currency_spacing: int | None = ...,
+    preserve_posting_blank_lines: bool | None = ...,
+    currency_position: str | None = ...,
+    normalize_include_paths: bool | None = ...,
+    prefix_width: int | None = ...,
+    verify_semantics: bool | None = ...,
+    use_tabs: bool | None = ...,
+    global_account_alignment: bool | None = ...,
+    respect_column_hints: bool | None = ...,
+    metadata_extra_indent: int | None = ...,
+    blank_lines: str | None = ...,
+    alignment_tolerance: int | None = ...,
+    sort_tags_links: bool | None = ...,
+    tag_link_grouping: str | None = ...,
+    date_separator: str | None = ...,
+    sort_directives: bool | None = ...,
+    sort_postings: bool | None = ...,
+    max_blank_lines: int | None = ...,
+    blank_lines_around_transactions: bool | None = ...,
+    align_custom: bool | None = ...,
+    comment_column: int | None = ...,
+    comment_anchoring: str | None = ...,
+    flag_in_margin: bool | None = ...,
+    long_comment_placement: str | None = ...,
+    normalize_metadata_dates: bool | None = ...,
+    price_operator_spacing: int | None = ...,
+    minimize_diff: bool | None = ...,
+    normalize_currency_case: bool | None = ...,
+    thousands_separator: str | None = ...,
+    trailing_newline: bool | None = ...,
 ) -> str: ...
 def main(argv: Sequence[str]) -> int: ...
@@ -0,0 +1,13 @@
+from beancount_format import check_text
+
+
+def test_check_text_accepts_formatted_input() -> None:
+    assert check_text("2010-01-01 open Assets:Cash\n") is True
+
+
+def test_check_text_rejects_unformatted_input() -> None:
+    assert check_text("2010-01-01 open\tAssets:Cash   \n") is False
+
+
+def test_check_text_uses_options() -> None:
+    assert check_text("2010-01-01 open Assets:Cash\n", new_line="crlf") is False
@@ -0,0 +1,37 @@
+from pathlib import Path
+
+import pytest
+
+from beancount_format import format_file
+
+UNFORMATTED = "2010-01-01 open\tAssets:Cash   \n"
+FORMATTED = "2010-01-01 open Assets:Cash\n"
+
+
+def test_format_file_returns_formatted_text(tmp_path: Path) -> None:
+    ledger = tmp_path / "ledger.beancount"
+    ledger.write_text(UNFORMATTED, encoding="utf-8")
+
+    assert format_file(ledger) == FORMATTED
+    assert ledger.read_text(encoding="utf-8") == UNFORMATTED
+
+
+def test_format_file_in_place_reports_changes(tmp_path: Path) -> None:
+    ledger = tmp_path / "ledger.beancount"
+    ledger.write_text(UNFORMATTED, encoding="utf-8")
+
+    assert format_file(ledger, in_place=True) is True
+    assert ledger.read_text(encoding="utf-8") == FORMATTED
+    assert format_file(ledger, in_place=True) is False
+
+
+def test_format_file_passes_options(tmp_path: Path) -> None:
+    ledger = tmp_path / "ledger.beancount"
+    ledger.write_text(UNFORMATTED, encoding="utf-8")
+
+    assert format_file(ledger, new_line="crlf") == FORMATTED.replace("\n", "\r\n")
+
+
+def test_format_file_missing_path(tmp_path: Path) -> None:
+    with pytest.raises(FileNotFoundError):
+        format_file(tmp_path / "missing.beancount")
@@ -0,0 +1,36 @@
+import pytest
+
+from beancount_format import format_text
+
+SAMPLE = '2010-01-01 * "Coffee"\n  Expenses:Food  3.50 USD\n  Assets:Cash\n'
+
+
+def test_currency_column_aligns_currencies() -> None:
+    formatted = format_text(SAMPLE, currency_column=40)
+    posting = formatted.splitlines()[1]
+    assert posting.index("USD") == 39
+
+
+def test_number_currency_spacing() -> None:
+    formatted = format_text(SAMPLE, number_currency_spacing=2)
+    assert "3.50  USD" in formatted
+
+
+def test_account_amount_spacing() -> None:
+    formatted = format_text(SAMPLE, account_amount_spacing=2)
+    assert "  Expenses:Food  3.50 USD\n" in formatted
+
+
+@pytest.mark.parametrize(
+    "option",
+    [
+        "currency_column",
+        "num_width",
+        "account_amount_spacing",
+        "number_currency_spacing",
+        "prefix_width",
+    ],
+)
+def test_negative_values_are_rejected(option: str) -> None:
+    with pytest.raises(ValueError, match=option):
+        format_text(SAMPLE, **{option: -1})
@@ -1,3 +1,7 @@
+from pathlib import Path
+
+import pytest
+
 from beancount_format import format_text
 
 
@@ -8,7 +12,19 @@ def test_round_trip_open_account() -> None:
 
 def test_round_trip_with_path() -> None:
     sample = "2010-01-01 open Assets:Cash\n"
-    assert format_text(sample, path="ledger.beancount") == sample
+    assert format_text(sample, filename="ledger.beancount") == sample
+
+
+def test_errors_name_the_filename() -> None:
+    sample = "2010-01-01 open Assets:Cash\n2010-01-06 price USD 10\n"
+    with pytest.raises(RuntimeError, match=r"ledger\.beancount.*line 2"):
+        format_text(sample, filename=Path("books/ledger.beancount"))
+
+
+def test_errors_default_to_example_beancount() -> None:
+    sample = "2010-01-01 open Assets:Cash\n2010-01-06 price USD 10\n"
+    with pytest.raises(RuntimeError, match=r"example\.beancount"):
+        format_text(sample)
 
 
 if __name__ == "__main__":
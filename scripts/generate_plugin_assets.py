@@ -19,6 +19,54 @@ class NewLineKind(str, Enum):
     CRLF = "crlf"
 
 
+class SameDateOrder(str, Enum):
+    SOURCE = "source"
+    TYPE_PRIORITY = "type_priority"
+
+
+class LongCurrency(str, Enum):
+    OVERFLOW = "overflow"
+    SHRINK = "shrink"
+
+
+class CurrencyPosition(str, Enum):
+    AFTER = "after"
+    BEFORE = "before"
+
+
+class BlankLines(str, Enum):
+    NORMALIZE = "normalize"
+    PRESERVE = "preserve"
+
+
+class TagLinkGrouping(str, Enum):
+    SOURCE = "source"
+    TAGS_FIRST = "tags_first"
+    LINKS_FIRST = "links_first"
+
+
+class ThousandsSeparator(str, Enum):
+    PRESERVE = "preserve"
+    NONE = "none"
+    COMMA = "comma"
+
+
+class DateSeparator(str, Enum):
+    DASH = "-"
+    SLASH = "/"
+
+
+class CommentAnchoring(str, Enum):
+    FOLLOWING = "following"
+    PRECEDING = "preceding"
+
+
+class LongCommentPlacement(str, Enum):
+    TRAILING = "trailing"
+    ABOVE = "above"
+    BELOW = "below"
+
+
 class DprintPluginSchema(BaseModel):
     model_config = ConfigDict(
         extra="forbid",
@@ -43,6 +91,188 @@ class DprintPluginSchema(BaseModel):
         default=False,
         description="When true, removes empty lines between consecutive balance directives.",
     )
+    minimal: bool = Field(
+        default=False,
+        description="When true, only normalizes whitespace and keeps columns and token spacing as written.",
+    )
+    normalize_comment_metadata: bool = Field(
+        default=False,
+        description="When true, trailing comments shaped like `key: value` get a single space after the colon.",
+    )
+    currency_column: int | None = Field(
+        default=None,
+        ge=1,
+        description="Align posting currencies to start at this 1-based column, counted from the start of the line including posting flags.",
+    )
+    collapse_string_spaces: bool = Field(
+        default=False,
+        description="When true, collapses runs of spaces inside payee and narration strings. This changes string content.",
+    )
+    align_keyword: bool = Field(
+        default=False,
+        description="When true, aligns the operands of consecutive commodity, pushtag, poptag and popmeta directives into one column.",
+    )
+    sort_by_date: bool = Field(
+        default=False,
+        description="When true, stably sorts dated directives by date between undated directives and standalone comments.",
+    )
+    same_date_order: SameDateOrder = Field(
+        default=SameDateOrder.SOURCE,
+        description="Tie-break for same-date directives when `sort_by_date` is enabled: keep source order or use beancount's type priority.",
+    )
+    trim_comment_trailing: bool = Field(
+        default=True,
+        description="When false, keeps trailing whitespace inside comment content instead of trimming it.",
+    )
+    align_text_directives: bool = Field(
+        default=False,
+        description="When true, aligns the trailing strings of consecutive text directives (such as event descriptions) into one column.",
+    )
+    preserve_leading_blank_lines: bool = Field(
+        default=False,
+        description="When true, keeps up to `max_blank_lines` blank lines before the first directive instead of removing them.",
+    )
+    num_width: int | None = Field(
+        default=None,
+        ge=1,
+        description="Right-align the number of every amount in a field of this many characters before its currency.",
+    )
+    account_amount_spacing: int | None = Field(
+        default=None,
+        ge=1,
+        description="Put every posting amount exactly this many spaces after its own account, disabling amount alignment (including `currency_column`).",
+    )
+    long_currency: LongCurrency = Field(
+        default=LongCurrency.OVERFLOW,
+        description="With `currency_column`, how to handle amounts that would run past `line_width`: keep the column and overflow, or shrink the padding.",
+    )
+    number_currency_spacing: int = Field(
+        default=1,
+        ge=1,
+        description="Number of spaces between the number of an amount and its currency.",
+    )
+    preserve_posting_blank_lines: bool = Field(
+        default=False,
+        description="When true, keeps blank lines inside transactions (e.g. between metadata and the next posting) instead of removing them.",
+    )
+    currency_position: CurrencyPosition = Field(
+        default=CurrencyPosition.AFTER,
+        description="Write currencies after the number (beancount) or before it. `before` is for display exports only; the output is not valid beancount.",
+    )
+    normalize_include_paths: bool = Field(
+        default=False,
+        description="When true, rewrites backslashes in `include` filenames to forward slashes.",
+    )
+    prefix_width: int | None = Field(
+        default=None,
+        ge=1,
+        description="Pad the part of each posting before its amount (indent, flag and account) to this many columns. `account_amount_spacing` wins when set.",
+    )
+    verify_semantics: bool = Field(
+        default=False,
+        description="When true, re-parses the formatted output and fails if any directive differs from the input beyond whitespace and comments.",
+    )
+    use_tabs: bool = Field(
+        default=False,
+        description="When true, indents postings and metadata with one tab per indent level instead of spaces.",
+    )
+    global_account_alignment: bool = Field(
+        default=False,
+        description="When true, posting amounts start after the longest posting account in the whole file instead of per transaction.",
+    )
+    respect_column_hints: bool = Field(
+        default=False,
+        description="When true, a `; col:N` comment directly above a directive sets its currency column, overriding `currency_column` for that directive only.",
+    )
+    metadata_extra_indent: int = Field(
+        default=0,
+        ge=0,
+        description="When non-zero, metadata is re-indented this many spaces deeper than `indent_width` below its directive or posting. `0` keeps the source indentation.",
+    )
+    blank_lines: BlankLines = Field(
+        default=BlankLines.NORMALIZE,
+        description="`normalize` keeps up to `max_blank_lines` source blank lines between directives but at least one around transactions; `preserve` keeps the source count (up to `max_blank_lines`) exactly.",
+    )
+    alignment_tolerance: int = Field(
+        default=0,
+        ge=0,
+        description="Leave a posting amount at its written column when it is within this many columns of its aligned position.",
+    )
+    sort_tags_links: bool = Field(
+        default=False,
+        description="When true, sorts the tags and links after a transaction or document header alphabetically (tags before links, within each group of `tag_link_grouping`) and drops exact repeats.",
+    )
+    tag_link_grouping: TagLinkGrouping = Field(
+        default=TagLinkGrouping.SOURCE,
+        description="Group the tags and links after a header: `source` keeps the written order, `tags_first` or `links_first` puts one kind before the other.",
+    )
+    date_separator: DateSeparator = Field(
+        default=DateSeparator.DASH,
+        description="Separator written between the year, month and day of directive dates: `-` or `/`. Malformed dates are left as written.",
+    )
+    sort_directives: bool = Field(
+        default=False,
+        description="When true, moves option, plugin and include directives to the top in source order and stably sorts dated directives by date.",
+    )
+    sort_postings: bool = Field(
+        default=False,
+        description="When true, stably sorts the postings of each transaction by account name, keeping their metadata with them.",
+    )
+    max_blank_lines: int = Field(
+        default=2,
+        ge=0,
+        description="Keep at most this many consecutive blank lines between directives.",
+    )
+    blank_lines_around_transactions: bool = Field(
+        default=True,
+        description="When true and `blank_lines` is `normalize`, puts at least one blank line around each transaction.",
+    )
+    align_custom: bool = Field(
+        default=False,
+        description="When true, aligns the values of consecutive custom directives into one column after the widest name.",
+    )
+    comment_column: int | None = Field(
+        default=None,
+        ge=1,
+        description="Start every inline comment at this 1-based column. Lines already reaching it get a single space before the comment.",
+    )
+    comment_anchoring: CommentAnchoring = Field(
+        default=CommentAnchoring.FOLLOWING,
+        description="When sorting, comment lines move with the directive directly below them (`following`) or directly above them (`preceding`).",
+    )
+    flag_in_margin: bool = Field(
+        default=False,
+        description="When true, writes posting flags inside the indentation so flagged and unflagged accounts start at the same column.",
+    )
+    long_comment_placement: LongCommentPlacement = Field(
+        default=LongCommentPlacement.TRAILING,
+        description="Where an inline comment goes when it doesn't fit within line_width: kept at the end of the line (`trailing`) or moved to its own line `above` or `below` it.",
+    )
+    normalize_metadata_dates: bool = Field(
+        default=False,
+        description="When true, date values of metadata and custom directives are also written with date_separator. Strings and other values are kept.",
+    )
+    price_operator_spacing: int = Field(
+        default=1,
+        ge=0,
+        description="Number of spaces between a posting's price operator (`@` or `@@`) and the price after it.",
+    )
+    minimize_diff: bool = Field(
+        default=False,
+        description="When true, directives whose formatting would only change the whitespace between tokens are kept as written, to keep diffs small when adopting the formatter.",
+    )
+    normalize_currency_case: bool = Field(
+        default=False,
+        description="When true, currencies are uppercased. A currency that isn't valid once uppercased is kept as written.",
+    )
+    thousands_separator: ThousandsSeparator = Field(
+        default=ThousandsSeparator.PRESERVE,
+        description="How `,` thousands separators in numbers are written: kept as written (`preserve`), removed (`none`) or inserted every three integer digits (`comma`).",
+    )
+    trailing_newline: bool = Field(
+        default=True,
+        description="When false, the final newline of the output is dropped, e.g. to embed formatted text in another document.",
+    )
 
 
 def build_schema() -> dict[str, Any]: